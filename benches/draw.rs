@@ -0,0 +1,55 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tim::image::{Charset, RenderCache, RenderOptions};
+use tim::Image;
+
+const SIZES: &[(usize, usize)] = &[(80, 24), (200, 60), (400, 120)];
+const ZOOMS: &[f32] = &[0.25, 1.0, 4.0];
+const BUDGET: Duration = Duration::from_millis(500);
+
+fn image(width: u32, height: u32) -> Image {
+    let im = image::RgbaImage::from_fn(width, height, |x, y| {
+        let (u, v) = (x * 255 / width, y * 255 / height);
+        image::Rgba([u as u8, v as u8, ((x ^ y) & 0xff) as u8, if (x / 16 + y / 16) % 5 == 0 { 128 } else { 255 }])
+    });
+    Image::new(image::DynamicImage::ImageRgba8(im)).unwrap()
+}
+
+fn bench(name: &str, mut f: impl FnMut() -> usize) {
+    let start = Instant::now();
+    let mut iters = 0u32;
+    let mut bytes = 0;
+    while iters == 0 || start.elapsed() < BUDGET {
+        bytes = black_box(f());
+        iters += 1;
+    }
+    let per = start.elapsed() / iters;
+    println!("{:<36} {:>10.3} ms/iter {:>10} bytes ({} iters)", name, per.as_secs_f64() * 1000.0, bytes, iters);
+}
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    for charset in [Charset::HalfBlock, Charset::Ascii] {
+        let options = RenderOptions { charset, ..RenderOptions::default() };
+        for &(cols, rows) in SIZES {
+            for &zoom in ZOOMS {
+                let im = image((cols as f32 / zoom) as u32, (rows as f32 * 2.0 / zoom) as u32);
+                let mut warm = RenderCache::default();
+                for keep in [false, true] {
+                    let name = format!("{:?}/zoom-{}/{}x{}/{}", charset, zoom, cols, rows, if keep { "warm" } else { "cold" });
+                    if filter.as_ref().is_some_and(|f| !name.contains(f.as_str())) {
+                        continue;
+                    }
+                    bench(&name, || {
+                        let mut out = Vec::new();
+                        let mut cold = RenderCache::default();
+                        let cache = if keep { &mut warm } else { &mut cold };
+                        im.draw(&mut out, cache, &options, (cols, rows), (0, 0), (zoom, zoom)).unwrap();
+                        out.len()
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+
+use tim::color::{self, Colors};
+use tim::Pixel;
+use tim::image::{Background, Charset, Crop, Glyph, Protocol, Raw, Sampling, Simulation, ToneMap};
+
+use crate::gallery;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Fit {
+    #[default]
+    Contain,
+    Width,
+    Height,
+    Fill,
+    Stretch,
+    Center,
+    None,
+}
+
+impl std::str::FromStr for Fit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fit" => Ok(Self::Contain),
+            "fill" => Ok(Self::Fill),
+            "stretch" => Ok(Self::Stretch),
+            "center" => Ok(Self::Center),
+            "none" => Ok(Self::None),
+            _ => bail!("unknown scale mode: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct View {
+    pub fit: Fit,
+    pub zoom: Option<f32>,
+    pub pan: Option<(usize, usize)>,
+    pub sheet: Option<usize>,
+    pub compare: bool,
+    pub natural_scroll: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Playback {
+    pub slideshow: Option<Duration>,
+    pub ken_burns: bool,
+    pub speed: f32,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self { slideshow: None, ken_burns: false, speed: 1.0 }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub view: View,
+    pub protocol: Option<Protocol>,
+    pub colors: Option<Colors>,
+    pub ansi16: Option<[Pixel; 16]>,
+    pub charset: Charset,
+    pub glyph: Glyph,
+    pub keep_color: bool,
+    pub background: Background,
+    pub pad_color: Option<Pixel>,
+    pub crop: Option<Crop>,
+    pub trim: Option<u8>,
+    pub rotate: u8,
+    pub raw: Option<Raw>,
+    pub dicom: bool,
+    pub cell_aspect: Option<f32>,
+    pub playback: Playback,
+    pub max_pixels: Option<u64>,
+    pub cache: Option<usize>,
+    pub preload: bool,
+    pub fast_jpeg: bool,
+    pub linear: bool,
+    pub sampling: Sampling,
+    pub tone_map: ToneMap,
+    pub simulation: Simulation,
+    pub threshold: Option<u8>,
+    pub sharpen: Option<f32>,
+    pub watch: bool,
+    pub log: Option<String>,
+    pub no_alt_screen: bool,
+    pub recursive: bool,
+    pub wrap: bool,
+    pub print: bool,
+    pub links: bool,
+    pub palette: Option<usize>,
+    pub diff: Option<(String, String)>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub size: Option<(usize, usize)>,
+    pub paths: Vec<String>,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--print" => parsed.print = true,
+                "--links" => parsed.links = true,
+                "--clipboard" => parsed.paths.push(gallery::CLIPBOARD.into()),
+                "--compare" => {
+                    parsed.paths.push(value(&mut args, &arg)?);
+                    parsed.paths.push(value(&mut args, &arg)?);
+                    parsed.view.compare = true;
+                },
+                "--diff" => parsed.diff = Some((value(&mut args, &arg)?, value(&mut args, &arg)?)),
+                "--palette" => parsed.palette = Some(size(&value(&mut args, &arg)?)?),
+                "--recursive" => parsed.recursive = true,
+                "--wrap" => parsed.wrap = true,
+                "--preload" => parsed.preload = true,
+                "--fast-jpeg" => parsed.fast_jpeg = true,
+                "--linear" => parsed.linear = true,
+                "--sampling" => parsed.sampling = value(&mut args, &arg)?.parse()?,
+                "--hdr-normalize" => parsed.tone_map = ToneMap::Normalize,
+                "--tone-map" => parsed.tone_map = value(&mut args, &arg)?.parse()?,
+                "--simulate" => parsed.simulation = value(&mut args, &arg)?.parse()?,
+                "--threshold" => parsed.threshold = Some(value(&mut args, &arg)?.parse()?),
+                "--sharpen" => {
+                    let amount: f32 = value(&mut args, &arg)?.parse()?;
+                    if !(amount.is_finite() && amount >= 0.0) {
+                        bail!("invalid sharpen amount: {}", amount);
+                    }
+                    parsed.sharpen = Some(amount);
+                },
+                "--watch" => parsed.watch = true,
+                "--log" => parsed.log = Some(value(&mut args, &arg)?),
+                "--no-alt-screen" => parsed.no_alt_screen = true,
+                "--fit-width" => parsed.view.fit = Fit::Width,
+                "--fit-height" => parsed.view.fit = Fit::Height,
+                "--natural-scroll" | "--invert-scroll" => parsed.view.natural_scroll = true,
+                "--scale-mode" => parsed.view.fit = value(&mut args, &arg)?.parse()?,
+                "--zoom" => {
+                    let zoom: f32 = value(&mut args, &arg)?.parse()?;
+                    if !(zoom.is_finite() && zoom > 0.0) {
+                        bail!("invalid zoom: {}", zoom);
+                    }
+                    parsed.view.zoom = Some(zoom);
+                },
+                "--grid" => parsed.view.sheet = Some(size(&value(&mut args, &arg)?)?),
+                "--pan" => parsed.view.pan = Some(pan(&value(&mut args, &arg)?)?),
+                "--protocol" => parsed.protocol = Some(value(&mut args, &arg)?.parse()?),
+                "--colors" | "--color-depth" => parsed.colors = Some(value(&mut args, &arg)?.parse()?),
+                "--ansi-colors" => parsed.ansi16 = Some(color::parse_palette(&value(&mut args, &arg)?)?),
+                "--charset" | "--mode" => parsed.charset = value(&mut args, &arg)?.parse()?,
+                "--glyph" => parsed.glyph = value(&mut args, &arg)?.parse()?,
+                "--keep-color" => parsed.keep_color = true,
+                "--bg" => parsed.background = value(&mut args, &arg)?.parse()?,
+                "--pad-color" => parsed.pad_color = Some(value(&mut args, &arg)?.parse()?),
+                "--transparency-grid" => parsed.background = Background::Checkerboard,
+                "--crop" => parsed.crop = Some(value(&mut args, &arg)?.parse()?),
+                "--format" => match value(&mut args, &arg)?.as_str() {
+                    "dicom" => parsed.dicom = true,
+                    format => bail!("unknown format: {}", format),
+                },
+                "--raw" => parsed.raw = Some(value(&mut args, &arg)?.parse()?),
+                "--rotate" => {
+                    let degrees: i32 = value(&mut args, &arg)?.parse()?;
+                    if degrees % 90 != 0 {
+                        bail!("invalid rotation (expected a multiple of 90): {}", degrees);
+                    }
+                    parsed.rotate = (degrees / 90).rem_euclid(4) as u8;
+                },
+                "--trim" => parsed.trim = parsed.trim.or(Some(0)),
+                "--trim-threshold" => parsed.trim = Some(value(&mut args, &arg)?.parse()?),
+                "--cell-aspect" => {
+                    let aspect: f32 = value(&mut args, &arg)?.parse()?;
+                    if !(aspect.is_finite() && aspect > 0.0) {
+                        bail!("invalid cell aspect: {}", aspect);
+                    }
+                    parsed.cell_aspect = Some(aspect);
+                },
+                "--slideshow" => {
+                    let secs: f32 = value(&mut args, &arg)?.parse()?;
+                    if !(secs.is_finite() && secs > 0.0) {
+                        bail!("invalid slideshow interval: {}", secs);
+                    }
+                    parsed.playback.slideshow = Some(Duration::from_secs_f32(secs));
+                },
+                "--ken-burns" => parsed.playback.ken_burns = true,
+                "--speed" => {
+                    let speed: f32 = value(&mut args, &arg)?.parse()?;
+                    if !(speed.is_finite() && speed > 0.0) {
+                        bail!("invalid speed: {}", speed);
+                    }
+                    parsed.playback.speed = speed;
+                },
+                "--width" => parsed.width = Some(size(&value(&mut args, &arg)?)?),
+                "--height" => parsed.height = Some(size(&value(&mut args, &arg)?)?),
+                "--size" => parsed.size = Some(grid(&value(&mut args, &arg)?)?),
+                "--max-decode-pixels" => parsed.max_pixels = Some(value(&mut args, &arg)?.parse()?),
+                "--cache" => parsed.cache = Some(value(&mut args, &arg)?.parse()?),
+                _ if arg.starts_with("--") => bail!("unknown option: {}", arg),
+                _ => parsed.paths.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, name: &str) -> Result<String> {
+    args.next().ok_or_else(|| anyhow!("missing value for {}", name))
+}
+
+fn size(s: &str) -> Result<usize> {
+    match s.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => bail!("invalid size: {}", s),
+    }
+}
+
+fn grid(s: &str) -> Result<(usize, usize)> {
+    match s.split_once('x').map(|(cols, rows)| (size(cols.trim()), size(rows.trim()))) {
+        Some((Ok(cols), Ok(rows))) => Ok((cols, rows)),
+        _ => bail!("invalid size (expected COLSxROWS): {}", s),
+    }
+}
+
+fn pan(s: &str) -> Result<(usize, usize)> {
+    match s.split_once(',').map(|(x, y)| (x.trim().parse(), y.trim().parse())) {
+        Some((Ok(x), Ok(y))) => Ok((x, y)),
+        _ => bail!("invalid pan (expected X,Y): {}", s),
+    }
+}
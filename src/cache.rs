@@ -0,0 +1,50 @@
+use tim::image::Image;
+
+pub const DEFAULT_CAPACITY: usize = 16;
+
+pub struct Cache {
+    capacity: usize,
+    entries: Vec<(String, Image)>,
+}
+
+impl Cache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.iter().any(|(p, _)| p == path)
+    }
+
+    pub fn get(&mut self, path: &str) -> Option<&mut Image> {
+        let i = self.entries.iter().position(|(p, _)| p == path)?;
+        let entry = self.entries.remove(i);
+        self.entries.push(entry);
+        self.entries.last_mut().map(|(_, im)| im)
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<Image> {
+        let i = self.entries.iter().position(|(p, _)| p == path)?;
+        Some(self.entries.remove(i).1)
+    }
+
+    pub fn insert(&mut self, path: String, im: Image) {
+        self.entries.retain(|(p, _)| *p != path);
+        self.entries.push((path, im));
+        while self.capacity > 0 && self.entries.len() > self.capacity {
+            match self.entries.iter().position(|(p, _)| p != "-") {
+                Some(i) if i + 1 < self.entries.len() => {
+                    self.entries.remove(i);
+                },
+                _ => break,
+            }
+        }
+    }
+}
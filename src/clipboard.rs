@@ -0,0 +1,56 @@
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+const TOOLS: &[&[&str]] = &[
+    &["pbcopy"],
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+];
+
+const PASTE_TOOLS: &[&[&str]] = &[
+    &["pngpaste", "-"],
+    &["wl-paste", "--no-newline", "--type", "image/png"],
+    &["xclip", "-selection", "clipboard", "-target", "image/png", "-out"],
+];
+
+pub fn copy(text: &str) -> Result<()> {
+    let mut failed = Vec::new();
+    for tool in TOOLS {
+        let mut child = match Command::new(tool[0]).args(&tool[1..]).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context(format!("failed to run {}", tool[0])),
+        };
+        let written = child.stdin.take().expect("stdin is piped").write_all(text.as_bytes());
+        if child.wait()?.success() && written.is_ok() {
+            return Ok(());
+        }
+        failed.push(tool[0]);
+    }
+    if !failed.is_empty() {
+        bail!("{} failed", failed.join(", "));
+    }
+    bail!("no clipboard tool found (tried pbcopy, wl-copy, xclip and xsel)")
+}
+
+pub fn paste() -> Result<Vec<u8>> {
+    let mut found = false;
+    for tool in PASTE_TOOLS {
+        let output = match Command::new(tool[0]).args(&tool[1..]).stdin(Stdio::null()).stderr(Stdio::null()).output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context(format!("failed to run {}", tool[0])),
+        };
+        found = true;
+        if output.status.success() && !output.stdout.is_empty() {
+            return Ok(output.stdout);
+        }
+    }
+    if found {
+        bail!("the clipboard does not contain an image");
+    }
+    bail!("no clipboard tool found (tried pngpaste, wl-paste and xclip)")
+}
@@ -0,0 +1,190 @@
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+
+use crate::image::Pixel;
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+pub const ANSI16: [Pixel; 16] = [
+    Pixel { r: 0, g: 0, b: 0, a: 255 },
+    Pixel { r: 205, g: 0, b: 0, a: 255 },
+    Pixel { r: 0, g: 205, b: 0, a: 255 },
+    Pixel { r: 205, g: 205, b: 0, a: 255 },
+    Pixel { r: 0, g: 0, b: 238, a: 255 },
+    Pixel { r: 205, g: 0, b: 205, a: 255 },
+    Pixel { r: 0, g: 205, b: 205, a: 255 },
+    Pixel { r: 229, g: 229, b: 229, a: 255 },
+    Pixel { r: 127, g: 127, b: 127, a: 255 },
+    Pixel { r: 255, g: 0, b: 0, a: 255 },
+    Pixel { r: 0, g: 255, b: 0, a: 255 },
+    Pixel { r: 255, g: 255, b: 0, a: 255 },
+    Pixel { r: 92, g: 92, b: 255, a: 255 },
+    Pixel { r: 255, g: 0, b: 255, a: 255 },
+    Pixel { r: 0, g: 255, b: 255, a: 255 },
+    Pixel { r: 255, g: 255, b: 255, a: 255 },
+];
+const MONO_THRESHOLD: i32 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Colors {
+    #[default]
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    Mono,
+}
+
+impl Colors {
+    pub fn detect() -> Self {
+        match std::env::var("COLORTERM").as_deref() {
+            Ok("truecolor") | Ok("24bit") => Self::TrueColor,
+            _ => Self::Ansi256,
+        }
+    }
+}
+
+impl std::str::FromStr for Colors {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "truecolor" | "24bit" => Ok(Self::TrueColor),
+            "256" => Ok(Self::Ansi256),
+            "16" => Ok(Self::Ansi16),
+            "mono" => Ok(Self::Mono),
+            _ => bail!("unsupported color count: {}", s),
+        }
+    }
+}
+
+fn cube_index(value: i32) -> usize {
+    if value < 48 {
+        0
+    } else if value < 115 {
+        1
+    } else {
+        (((value - 35) / 40) as usize).min(5)
+    }
+}
+
+fn distance(a: (i32, i32, i32), b: (i32, i32, i32)) -> i32 {
+    let dr = a.0 - b.0;
+    let dg = a.1 - b.1;
+    let db = a.2 - b.2;
+    (dr * dr) + (dg * dg) + (db * db)
+}
+
+pub fn ansi256_rgb(index: u8) -> (i32, i32, i32) {
+    if index >= 232 {
+        let v = 8 + ((index as i32 - 232) * 10);
+        (v, v, v)
+    } else if index >= 16 {
+        let i = index as usize - 16;
+        (CUBE_LEVELS[i / 36] as i32, CUBE_LEVELS[(i / 6) % 6] as i32, CUBE_LEVELS[i % 6] as i32)
+    } else {
+        (0, 0, 0)
+    }
+}
+
+pub fn nearest_ansi256(rgb: (i32, i32, i32)) -> u8 {
+    let (r, g, b) = (cube_index(rgb.0), cube_index(rgb.1), cube_index(rgb.2));
+    let cube = (16 + (r * 36) + (g * 6) + b) as u8;
+
+    let avg = (rgb.0 + rgb.1 + rgb.2) / 3;
+    let gray = if avg < 8 {
+        232
+    } else {
+        (232 + ((avg - 3) / 10).min(23)) as u8
+    };
+
+    if distance(ansi256_rgb(gray), rgb) < distance(ansi256_rgb(cube), rgb) {
+        gray
+    } else {
+        cube
+    }
+}
+
+pub(crate) fn to_linear(value: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let v = i as f32 / 255.0;
+            if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+        })
+    })[value as usize]
+}
+
+pub(crate) fn from_linear(value: f32) -> u8 {
+    let v = if value <= 0.0031308 { value * 12.92 } else { (1.055 * value.powf(1.0 / 2.4)) - 0.055 };
+    (v * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+pub fn to_lab(pix: Pixel) -> (f32, f32, f32) {
+    let (r, g, b) = (to_linear(pix.r), to_linear(pix.g), to_linear(pix.b));
+    let x = ((0.4124 * r) + (0.3576 * g) + (0.1805 * b)) / 0.95047;
+    let y = (0.2126 * r) + (0.7152 * g) + (0.0722 * b);
+    let z = ((0.0193 * r) + (0.1192 * g) + (0.9505 * b)) / 1.08883;
+    let f = |t: f32| if t > 0.008856 { t.cbrt() } else { (7.787 * t) + (16.0 / 116.0) };
+    ((116.0 * f(y)) - 16.0, 500.0 * (f(x) - f(y)), 200.0 * (f(y) - f(z)))
+}
+
+fn lab_distance(a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+    let (dl, da, db) = (a.0 - b.0, a.1 - b.1, a.2 - b.2);
+    (dl * dl) + (da * da) + (db * db)
+}
+
+pub fn nearest_ansi16(pix: Pixel, palette: &[Pixel; 16]) -> u8 {
+    let lab = to_lab(pix);
+    (0..palette.len())
+        .map(|i| (i, lab_distance(to_lab(palette[i]), lab)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i as u8)
+}
+
+pub fn parse_palette(s: &str) -> Result<[Pixel; 16]> {
+    let colors = s.split(',').map(|c| c.trim().parse()).collect::<Result<Vec<Pixel>>>()?;
+    match colors.try_into() {
+        Ok(palette) => Ok(palette),
+        Err(colors) => bail!("expected 16 colors, got {}", colors.len()),
+    }
+}
+
+pub fn mono(rgb: (i32, i32, i32)) -> bool {
+    (rgb.0 * 299 + rgb.1 * 587 + rgb.2 * 114) / 1000 >= MONO_THRESHOLD
+}
+
+fn diffuse(slot: &mut (i32, i32, i32), err: (i32, i32, i32), weight: i32) {
+    slot.0 += err.0 * weight;
+    slot.1 += err.1 * weight;
+    slot.2 += err.2 * weight;
+}
+
+pub fn dither(pixels: &[Pixel], width: usize, height: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(width * height);
+    let mut cur = vec![(0, 0, 0); width + 2];
+    let mut next = vec![(0, 0, 0); width + 2];
+
+    for y in 0..height {
+        for x in 0..width {
+            let pix = &pixels[(y * width) + x];
+            let e = cur[x + 1];
+            let want = (
+                (pix.r as i32 + (e.0 / 16)).clamp(0, 255),
+                (pix.g as i32 + (e.1 / 16)).clamp(0, 255),
+                (pix.b as i32 + (e.2 / 16)).clamp(0, 255),
+            );
+            let index = nearest_ansi256(want);
+            let got = ansi256_rgb(index);
+            let err = (want.0 - got.0, want.1 - got.1, want.2 - got.2);
+            diffuse(&mut cur[x + 2], err, 7);
+            diffuse(&mut next[x], err, 3);
+            diffuse(&mut next[x + 1], err, 5);
+            diffuse(&mut next[x + 2], err, 1);
+            out.push(index);
+        }
+        std::mem::swap(&mut cur, &mut next);
+        next.fill((0, 0, 0));
+    }
+
+    out
+}
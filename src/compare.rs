@@ -0,0 +1,141 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyEventKind},
+    style,
+    terminal,
+    execute,
+    queue,
+};
+
+use tim::image::{self, Image, RenderCache, RenderOptions};
+
+use crate::args::Fit;
+use crate::gallery::Gallery;
+use crate::keys::{Action, Keymap};
+
+type View = ((f32, f32), (usize, usize));
+
+fn apply(im: &Image, view: &mut View, action: Action, twidth: usize, theight: usize) {
+    let (zoom, pos) = view;
+    match action {
+        Action::ZoomIn | Action::ZoomOut | Action::ZoomInLarge | Action::ZoomOutLarge => {
+            let focus = crate::view_center(im, *zoom, *pos, twidth, theight);
+            let step = if matches!(action, Action::ZoomInLarge | Action::ZoomOutLarge) { crate::ZOOM_STEP_LARGE } else { crate::ZOOM_STEP };
+            let new_zoom = if matches!(action, Action::ZoomIn | Action::ZoomInLarge) {
+                crate::clamp_zoom((zoom.0 * step, zoom.1 * step), twidth, theight)
+            } else {
+                ((zoom.0 / step).max(crate::MIN_ZOOM), (zoom.1 / step).max(crate::MIN_ZOOM))
+            };
+            *pos = crate::center_on(im, new_zoom, focus, twidth, theight);
+            *zoom = new_zoom;
+        },
+        Action::ActualSize => {
+            let focus = crate::view_center(im, *zoom, *pos, twidth, theight);
+            *pos = crate::center_on(im, (1.0, 1.0), focus, twidth, theight);
+            *zoom = (1.0, 1.0);
+        },
+        Action::PanLeft => pos.0 = pos.0.saturating_sub(1),
+        Action::PanRight => pos.0 += 1,
+        Action::PanUp => pos.1 += 1,
+        Action::PanDown => pos.1 = pos.1.saturating_sub(1),
+        Action::LeftEdge => pos.0 = 0,
+        Action::RightEdge => pos.0 = im.size(*zoom).0,
+        Action::Top => pos.1 = 0,
+        Action::Bottom => pos.1 = im.size(*zoom).1,
+        _ => {},
+    }
+}
+
+pub fn run<W: Write>(term: &mut W, gallery: &mut Gallery, options: &RenderOptions) -> Result<()> {
+    let labels = [gallery.paths()[0].clone(), gallery.paths()[1].clone()];
+    let (Some(left), Some(right)) = (gallery.take(0), gallery.take(1)) else {
+        bail!("both images must load to compare them");
+    };
+    let images = [left, right];
+    let keymap = Keymap::load()?;
+    let mut caches = [RenderCache::default(), RenderCache::default()];
+    let mut views: [View; 2] = [((1.0, 1.0), (0, 0)); 2];
+    let mut sync = true;
+    let mut active = 0;
+    let mut reset = true;
+    let mut pending_key = None;
+    let mut replay = None;
+
+    execute!(term, terminal::SetTitle(format!("{} | {}", labels[0], labels[1])))?;
+    loop {
+        let (cols, rows) = image::terminal_size()?;
+        let half = cols.saturating_sub(1) / 2;
+        let theight = rows.saturating_sub(1) * 2;
+
+        if reset {
+            reset = false;
+            views = [0, 1].map(|i| crate::fit_view(&images[i], half, theight, Fit::Contain));
+            if sync {
+                let zoom = views[0].0 .0.min(views[1].0 .0);
+                views = [((zoom, zoom), (0, 0)); 2];
+            }
+            execute!(term, terminal::Clear(terminal::ClearType::All))?;
+            caches.iter_mut().for_each(RenderCache::force_full);
+        }
+
+        for (i, im) in images.iter().enumerate() {
+            let (zoom, pos) = &mut views[i];
+            let (iwidth, iheight) = im.size(*zoom);
+            pos.0 = pos.0.min(iwidth.saturating_sub(half));
+            pos.1 = pos.1.min(iheight.saturating_sub(theight));
+            caches[i].set_origin((i * (half + 1), 0));
+            im.draw(term, &mut caches[i], options, (half, rows.saturating_sub(1)), *pos, *zoom)?;
+        }
+        for y in 0..rows.saturating_sub(1) {
+            queue!(term, cursor::MoveTo(half as u16, y as u16), style::Print('│'))?;
+        }
+        let mode = if sync { "synchronized".to_string() } else { format!("independent, {} side active", ["left", "right"][active]) };
+        let text = format!("{} | {} zoom={} ({})", labels[0], labels[1], crate::zoom_text(views[active].0), mode);
+        crate::draw_status(term, cols, rows.saturating_sub(1), &text)?;
+        term.flush()?;
+
+        let event = match replay.take() {
+            Some(event) => event,
+            None => event::read()?,
+        };
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                let (action, again) = keymap.lookup(&mut pending_key, key.into(), |action| action != Action::Open && (action != Action::SwitchSide || !sync));
+                if again {
+                    replay = Some(event);
+                }
+                match action {
+                    Some(Action::Quit) => break,
+                    Some(Action::Reset) => reset = true,
+                    Some(Action::Redraw) => {
+                        execute!(term, terminal::Clear(terminal::ClearType::All))?;
+                        caches.iter_mut().for_each(RenderCache::force_full);
+                    },
+                    Some(Action::ToggleSync) => {
+                        sync = !sync;
+                        if sync {
+                            views[1 - active] = views[active];
+                        }
+                    },
+                    Some(Action::SwitchSide) => active = 1 - active,
+                    Some(action) => {
+                        apply(&images[active], &mut views[active], action, half, theight);
+                        if sync {
+                            views[1 - active] = views[active];
+                        }
+                    },
+                    None => {},
+                }
+            },
+            Event::Resize(..) => {
+                execute!(term, terminal::Clear(terminal::ClearType::All))?;
+                caches.iter_mut().for_each(RenderCache::force_full);
+            },
+            _ => {},
+        }
+    }
+    Ok(())
+}
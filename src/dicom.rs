@@ -0,0 +1,255 @@
+use anyhow::{anyhow, bail, Result};
+
+const PREAMBLE: usize = 128;
+const MAGIC: &[u8] = b"DICM";
+const IMPLICIT_LE: &str = "1.2.840.10008.1.2";
+const EXPLICIT_LE: &str = "1.2.840.10008.1.2.1";
+const UNDEFINED: u32 = 0xffff_ffff;
+const MAX_DEPTH: usize = 64;
+
+const TRANSFER_SYNTAX: (u16, u16) = (0x0002, 0x0010);
+const SAMPLES_PER_PIXEL: (u16, u16) = (0x0028, 0x0002);
+const PHOTOMETRIC: (u16, u16) = (0x0028, 0x0004);
+const ROWS: (u16, u16) = (0x0028, 0x0010);
+const COLUMNS: (u16, u16) = (0x0028, 0x0011);
+const BITS_ALLOCATED: (u16, u16) = (0x0028, 0x0100);
+const BITS_STORED: (u16, u16) = (0x0028, 0x0101);
+const PIXEL_REPRESENTATION: (u16, u16) = (0x0028, 0x0103);
+const WINDOW_CENTER: (u16, u16) = (0x0028, 0x1050);
+const WINDOW_WIDTH: (u16, u16) = (0x0028, 0x1051);
+const RESCALE_INTERCEPT: (u16, u16) = (0x0028, 0x1052);
+const RESCALE_SLOPE: (u16, u16) = (0x0028, 0x1053);
+const PIXEL_DATA: (u16, u16) = (0x7fe0, 0x0010);
+const ITEM_DELIMITER: (u16, u16) = (0xfffe, 0xe00d);
+const SEQUENCE_DELIMITER: (u16, u16) = (0xfffe, 0xe0dd);
+
+const LONG_VRS: &[&[u8; 2]] = &[b"OB", b"OD", b"OF", b"OL", b"OV", b"OW", b"SQ", b"SV", b"UC", b"UN", b"UR", b"UT", b"UV"];
+
+pub fn is_dicom(data: &[u8]) -> bool {
+    data.get(PREAMBLE..(PREAMBLE + MAGIC.len())) == Some(MAGIC)
+}
+
+struct Element<'a> {
+    tag: (u16, u16),
+    value: Option<&'a [u8]>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    explicit: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.data.get(self.pos..(self.pos + len)).ok_or_else(|| anyhow!("truncated DICOM data"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn group(&self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.data.get(self.pos..(self.pos + 2))?.try_into().ok()?))
+    }
+
+    fn next(&mut self) -> Result<Option<Element<'a>>> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let tag = (self.u16()?, self.u16()?);
+        let len = if tag.0 == 0xfffe {
+            self.u32()?
+        } else if self.explicit || tag.0 == 0x0002 {
+            let vr = self.bytes(2)?;
+            if LONG_VRS.iter().any(|long| long.as_slice() == vr) {
+                self.bytes(2)?;
+                self.u32()?
+            } else {
+                self.u16()? as u32
+            }
+        } else {
+            self.u32()?
+        };
+        let value = if len == UNDEFINED { None } else { Some(self.bytes(len as usize)?) };
+        Ok(Some(Element { tag, value }))
+    }
+
+    fn skip_undefined(&mut self, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!("DICOM sequences are nested too deeply");
+        }
+        while let Some(element) = self.next()? {
+            match element.tag {
+                ITEM_DELIMITER | SEQUENCE_DELIMITER => return Ok(()),
+                _ if element.value.is_none() => self.skip_undefined(depth + 1)?,
+                _ => {},
+            }
+        }
+        bail!("truncated DICOM data")
+    }
+}
+
+fn string(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string()
+}
+
+fn number(value: &[u8]) -> Option<f32> {
+    string(value).split('\\').next()?.trim().parse().ok()
+}
+
+fn uint(value: &[u8]) -> Result<u16> {
+    Ok(u16::from_le_bytes(value.get(0..2).ok_or_else(|| anyhow!("truncated DICOM data"))?.try_into()?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub center: f32,
+    pub width: f32,
+}
+
+pub struct Dicom {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    inverted: bool,
+    window: Window,
+}
+
+impl Dicom {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let start = if is_dicom(data) { PREAMBLE + MAGIC.len() } else { 0 };
+        let mut reader = Reader { data, pos: start, explicit: false };
+        let mut transfer_syntax = IMPLICIT_LE.to_string();
+        let (mut samples, mut photometric) = (1, "MONOCHROME2".to_string());
+        let (mut rows, mut columns) = (None, None);
+        let (mut bits, mut stored, mut signed) = (None, None, false);
+        let (mut center, mut width) = (None, None);
+        let (mut intercept, mut slope) = (0.0, 1.0);
+        let mut pixels = None;
+        let mut in_meta = true;
+
+        loop {
+            if in_meta && reader.group() != Some(0x0002) {
+                in_meta = false;
+                match transfer_syntax.as_str() {
+                    IMPLICIT_LE => {},
+                    EXPLICIT_LE => reader.explicit = true,
+                    other => bail!("unsupported DICOM transfer syntax: {}", other),
+                }
+            }
+            let Some(element) = reader.next()? else {
+                break;
+            };
+            let Some(value) = element.value else {
+                if element.tag == PIXEL_DATA {
+                    bail!("compressed DICOM pixel data is not supported");
+                }
+                reader.skip_undefined(0)?;
+                continue;
+            };
+            match element.tag {
+                TRANSFER_SYNTAX => transfer_syntax = string(value),
+                SAMPLES_PER_PIXEL => samples = uint(value)?,
+                PHOTOMETRIC => photometric = string(value),
+                ROWS => rows = Some(uint(value)? as usize),
+                COLUMNS => columns = Some(uint(value)? as usize),
+                BITS_ALLOCATED => bits = Some(uint(value)?),
+                BITS_STORED => stored = Some(uint(value)?),
+                PIXEL_REPRESENTATION => signed = uint(value)? == 1,
+                WINDOW_CENTER => center = number(value),
+                WINDOW_WIDTH => width = number(value),
+                RESCALE_INTERCEPT => intercept = number(value).unwrap_or(0.0),
+                RESCALE_SLOPE => slope = number(value).unwrap_or(1.0),
+                PIXEL_DATA => {
+                    pixels = Some(value);
+                    break;
+                },
+                _ => {},
+            }
+        }
+
+        if samples != 1 {
+            bail!("only grayscale DICOM images are supported");
+        }
+        let inverted = match photometric.as_str() {
+            "MONOCHROME1" => true,
+            "MONOCHROME2" => false,
+            other => bail!("unsupported DICOM photometric interpretation: {}", other),
+        };
+        let (Some(height), Some(columns), Some(pixels)) = (rows, columns, pixels) else {
+            bail!("DICOM file has no image");
+        };
+        let bits = bits.unwrap_or(16);
+        let bytes = match bits {
+            8 => 1,
+            16 => 2,
+            _ => bail!("unsupported DICOM bit depth: {}", bits),
+        };
+        let stored = stored.unwrap_or(bits).clamp(1, bits);
+        let count = columns * height;
+        if pixels.len() < count * bytes {
+            bail!("truncated DICOM pixel data");
+        }
+
+        let mask = (1u32 << stored) - 1;
+        let values: Vec<f32> = pixels
+            .chunks_exact(bytes)
+            .take(count)
+            .map(|chunk| {
+                let raw = if bytes == 1 { chunk[0] as u32 } else { u16::from_le_bytes([chunk[0], chunk[1]]) as u32 } & mask;
+                let value = if signed && raw >> (stored - 1) == 1 { raw as i32 - (1 << stored) } else { raw as i32 };
+                value as f32 * slope + intercept
+            })
+            .collect();
+
+        let window = match (center, width) {
+            (Some(center), Some(width)) if width > 0.0 => Window { center, width },
+            _ => {
+                let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                Window { center: (min + max) / 2.0, width: (max - min).max(1.0) }
+            },
+        };
+
+        Ok(Self { width: columns, height, values, inverted, window })
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    pub fn set_window(&mut self, window: Window) {
+        self.window = Window { center: window.center, width: window.width.max(1.0) };
+    }
+
+    pub fn render(&self) -> Vec<u8> {
+        let Window { center, width } = self.window;
+        let low = center - 0.5 - (width - 1.0) / 2.0;
+        let high = center - 0.5 + (width - 1.0) / 2.0;
+        self.values
+            .iter()
+            .map(|&value| {
+                let level = if value <= low {
+                    0.0
+                } else if value > high {
+                    255.0
+                } else {
+                    ((value - (center - 0.5)) / (width - 1.0).max(1.0) + 0.5) * 255.0
+                };
+                let level = level.round().clamp(0.0, 255.0) as u8;
+                if self.inverted { 255 - level } else { level }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,55 @@
+const ORIENTATION_TAG: u16 = 0x0112;
+
+fn u16_at(data: &[u8], pos: usize, le: bool) -> Option<u16> {
+    let bytes = [*data.get(pos)?, *data.get(pos + 1)?];
+    Some(if le { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+}
+
+fn u32_at(data: &[u8], pos: usize, le: bool) -> Option<u32> {
+    let bytes = [*data.get(pos)?, *data.get(pos + 1)?, *data.get(pos + 2)?, *data.get(pos + 3)?];
+    Some(if le { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let le = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    if u16_at(tiff, 2, le)? != 42 {
+        return None;
+    }
+
+    let ifd = u32_at(tiff, 4, le)? as usize;
+    let count = u16_at(tiff, ifd, le)? as usize;
+    for i in 0..count {
+        let entry = ifd + 2 + (i * 12);
+        if u16_at(tiff, entry, le)? == ORIENTATION_TAG {
+            return u16_at(tiff, entry + 8, le);
+        }
+    }
+    None
+}
+
+pub fn orientation(data: &[u8]) -> Option<u16> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return tiff_orientation(data);
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xff {
+            return None;
+        }
+        let marker = data[pos + 1];
+        if marker == 0xd9 || marker == 0xda {
+            return None;
+        }
+        let len = u16_at(data, pos + 2, false)? as usize;
+        if marker == 0xe1 && data.get((pos + 4)..(pos + 10)) == Some(b"Exif\0\0") {
+            return tiff_orientation(data.get((pos + 10)..(pos + 2 + len))?);
+        }
+        pos += 2 + len;
+    }
+    None
+}
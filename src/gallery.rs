@@ -0,0 +1,293 @@
+use std::io::Read;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use crate::cache::Cache;
+use crate::log;
+#[cfg(feature = "clipboard")]
+use crate::clipboard;
+#[cfg(feature = "net")]
+use crate::net;
+
+use tim::image::{Image, LoadOptions};
+
+pub struct Gallery {
+    paths: Vec<String>,
+    cache: Cache,
+    failed: Vec<bool>,
+    errors: Vec<(String, Error)>,
+    index: usize,
+    forward: bool,
+    options: LoadOptions,
+    preload: bool,
+    progress: bool,
+    wrap: bool,
+    pending: Option<(usize, Receiver<Result<Image>>)>,
+}
+
+const READ_CHUNK: usize = 64 * 1024;
+pub const CLIPBOARD: &str = "(clipboard)";
+
+pub fn read_all<R: Read>(mut reader: R, mut progress: impl FnMut(usize)) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut chunk = vec![0; READ_CHUNK];
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(data),
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        data.extend_from_slice(&chunk[..n]);
+        progress(data.len());
+    }
+}
+
+fn read(path: &str, options: &LoadOptions, progress: impl FnMut(usize)) -> Result<Image> {
+    #[cfg(feature = "net")]
+    if net::is_url(path) {
+        return Image::load(std::io::Cursor::new(net::fetch(path, progress)?), options);
+    }
+
+    #[cfg(feature = "clipboard")]
+    if path == CLIPBOARD {
+        return Image::load(std::io::Cursor::new(clipboard::paste()?), options);
+    }
+    #[cfg(not(feature = "clipboard"))]
+    if path == CLIPBOARD {
+        bail!("clipboard support is not enabled");
+    }
+
+    if path == "-" {
+        Image::load(std::io::Cursor::new(read_all(std::io::stdin(), progress)?), options)
+    } else {
+        Image::open(path, options)
+    }
+}
+
+pub fn load(path: &str, options: &LoadOptions, progress: bool) -> Result<Image> {
+    let start = Instant::now();
+    let result = if progress {
+        eprint!("Loading {}...", path);
+        let result = read(path, options, |bytes| eprint!("\r\x1b[KLoading {}... {} KiB", path, bytes / 1024));
+        eprint!("\r\x1b[K");
+        result
+    } else {
+        read(path, options, |_| {})
+    };
+    match &result {
+        Ok(im) => log::write(format_args!("{}: loaded {}x{} in {:?}", path, im.dimensions().0, im.dimensions().1, start.elapsed())),
+        Err(e) => log::write(format_args!("{}: {:#}", path, e)),
+    }
+    result
+}
+
+fn read_dir(dir: &Path, recursive: bool, paths: &mut Vec<String>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if recursive {
+                read_dir(&path, recursive, paths)?;
+            }
+        } else if ::image::ImageFormat::from_path(&path).is_ok() || (cfg!(feature = "dicom") && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dcm"))) {
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+pub fn expand(args: Vec<String>, recursive: bool) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for arg in args {
+        if arg != "-" && Path::new(&arg).is_dir() {
+            read_dir(Path::new(&arg), recursive, &mut paths).map_err(|e| anyhow!("{}: {}", arg, e))?;
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() {
+        bail!("no images found");
+    }
+    Ok(paths)
+}
+
+impl Gallery {
+    pub fn new(paths: Vec<String>, options: LoadOptions, capacity: usize) -> Self {
+        let failed = vec![false; paths.len()];
+        Self {
+            paths,
+            cache: Cache::new(capacity),
+            failed,
+            errors: Vec::new(),
+            index: 0,
+            forward: true,
+            options,
+            preload: false,
+            progress: false,
+            wrap: false,
+            pending: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index.min(self.paths.len().saturating_sub(1));
+        self.forward = true;
+    }
+
+    pub fn path(&self) -> &str {
+        &self.paths[self.index]
+    }
+
+    pub fn errors(&self) -> &[(String, Error)] {
+        &self.errors
+    }
+
+    pub fn set_preload(&mut self, preload: bool) {
+        self.preload = preload;
+    }
+
+    pub fn set_progress(&mut self, progress: bool) {
+        self.progress = progress;
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    pub fn preload_next(&mut self) {
+        self.finish_preload(false);
+        if !self.preload || self.pending.is_some() || self.paths.len() < 2 || self.cache.capacity() == 1 {
+            return;
+        }
+
+        let len = self.paths.len();
+        let index = if self.forward { (self.index + 1) % len } else { (self.index + len - 1) % len };
+        let path = self.paths[index].clone();
+        if path == "-" || self.failed[index] || self.cache.contains(&path) {
+            return;
+        }
+
+        let options = self.options.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(load(&path, &options, false));
+        });
+        self.pending = Some((index, rx));
+    }
+
+    fn finish_preload(&mut self, block: bool) {
+        let Some((index, rx)) = &self.pending else {
+            return;
+        };
+        let index = *index;
+        let result = if block {
+            rx.recv().ok()
+        } else {
+            match rx.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => None,
+            }
+        };
+        self.pending = None;
+
+        match result {
+            Some(Ok(im)) => self.cache.insert(self.paths[index].clone(), im),
+            Some(Err(e)) => {
+                self.failed[index] = true;
+                self.errors.push((self.paths[index].clone(), e));
+            },
+            None => {},
+        }
+    }
+
+    pub fn get(&mut self, index: usize) -> Option<&mut Image> {
+        if self.pending.as_ref().is_some_and(|(i, _)| *i == index) {
+            self.finish_preload(true);
+        }
+        let path = &self.paths[index];
+        if !self.cache.contains(path) && !self.failed[index] {
+            match load(path, &self.options, self.progress) {
+                Ok(im) => self.cache.insert(path.clone(), im),
+                Err(e) => {
+                    self.failed[index] = true;
+                    self.errors.push((path.clone(), e));
+                },
+            }
+        }
+        self.cache.get(&self.paths[index])
+    }
+
+    pub fn take(&mut self, index: usize) -> Option<Image> {
+        self.get(index)?;
+        self.cache.remove(&self.paths[index])
+    }
+
+    pub fn insert(&mut self, index: usize, im: Image) {
+        self.cache.insert(self.paths[index].clone(), im);
+    }
+
+    pub fn reload(&mut self) -> Result<()> {
+        let path = &self.paths[self.index];
+        let im = load(path, &self.options, false)?;
+        self.cache.insert(path.clone(), im);
+        Ok(())
+    }
+
+    pub fn current(&mut self) -> Result<&mut Image> {
+        let len = self.paths.len();
+        for i in 0..len {
+            let index = if self.forward { (self.index + i) % len } else { (self.index + len - i) % len };
+            if self.get(index).is_some() {
+                self.index = index;
+                return Ok(self.cache.get(&self.paths[index]).expect("image was just loaded"));
+            }
+        }
+        bail!("none of the images could be loaded")
+    }
+
+    pub fn step(&mut self, forward: bool) -> bool {
+        self.forward = forward;
+        let len = self.paths.len();
+        if self.wrap && len > 1 {
+            self.index = if forward { (self.index + 1) % len } else { (self.index + len - 1) % len };
+            true
+        } else if forward && self.index + 1 < len {
+            self.index += 1;
+            true
+        } else if !forward && self.index > 0 {
+            self.index -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn next_wrapping(&mut self) -> bool {
+        self.forward = true;
+        if self.paths.len() > 1 {
+            self.index = (self.index + 1) % self.paths.len();
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::color::{from_linear, to_linear};
+use crate::Pixel;
+
+const HEADER_SIZE: usize = 128;
+const TOLERANCE: f32 = 0.002;
+
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133856, -1.616867, -0.490615],
+    [-0.978768, 1.916142, 0.033454],
+    [0.071945, -0.228991, 1.405243],
+];
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    Ok(u16::from_be_bytes(data.get(pos..(pos + 2)).ok_or_else(|| anyhow!("truncated ICC profile"))?.try_into()?))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    Ok(u32::from_be_bytes(data.get(pos..(pos + 4)).ok_or_else(|| anyhow!("truncated ICC profile"))?.try_into()?))
+}
+
+fn fixed_at(data: &[u8], pos: usize) -> Result<f32> {
+    Ok(u32_at(data, pos)? as i32 as f32 / 65536.0)
+}
+
+fn curve(data: &[u8]) -> Result<[f32; 256]> {
+    let values: Vec<f32> = match data.get(0..4) {
+        Some(b"curv") => (0..u32_at(data, 8)? as usize).map(|i| Ok(u16_at(data, 12 + (i * 2))? as f32)).collect::<Result<_>>()?,
+        Some(b"para") => {
+            let kind = u16_at(data, 8)?;
+            let count = match kind {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => bail!("unsupported ICC parametric curve: {}", kind),
+            };
+            let p = (0..7).map(|i| if i < count { fixed_at(data, 12 + (i * 4)) } else { Ok(0.0) }).collect::<Result<Vec<f32>>>()?;
+            return Ok(std::array::from_fn(|i| {
+                let x = i as f32 / 255.0;
+                let (g, a, b, c, d, e, f) = (p[0], p[1], p[2], p[3], p[4], p[5], p[6]);
+                match kind {
+                    0 => x.powf(g),
+                    1 if x >= -b / a => (a * x + b).powf(g),
+                    1 => 0.0,
+                    2 if x >= -b / a => (a * x + b).powf(g) + c,
+                    2 => c,
+                    3 if x >= d => (a * x + b).powf(g),
+                    3 => c * x,
+                    _ if x >= d => (a * x + b).powf(g) + e,
+                    _ => c * x + f,
+                }
+            }));
+        },
+        _ => bail!("unsupported ICC curve type"),
+    };
+    Ok(std::array::from_fn(|i| {
+        let x = i as f32 / 255.0;
+        match values.len() {
+            0 => x,
+            1 => x.powf(values[0] / 256.0),
+            len => {
+                let pos = x * (len - 1) as f32;
+                let (lo, hi) = (pos.floor() as usize, (pos.ceil() as usize).min(len - 1));
+                (values[lo] + (values[hi] - values[lo]) * pos.fract()) / 65535.0
+            },
+        }
+    }))
+}
+
+pub struct Profile {
+    curves: [[f32; 256]; 3],
+    matrix: [[f32; 3]; 3],
+}
+
+impl Profile {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.get(36..40) != Some(b"acsp") {
+            bail!("not an ICC profile");
+        }
+        if data.get(16..20) != Some(b"RGB ") || data.get(20..24) != Some(b"XYZ ") {
+            bail!("only RGB matrix/TRC ICC profiles are supported");
+        }
+
+        let count = u32_at(data, HEADER_SIZE)? as usize;
+        let tag = |sig: &[u8; 4]| -> Result<&[u8]> {
+            for i in 0..count {
+                let entry = HEADER_SIZE + 4 + (i * 12);
+                if data.get(entry..(entry + 4)) == Some(sig.as_slice()) {
+                    let (offset, size) = (u32_at(data, entry + 4)? as usize, u32_at(data, entry + 8)? as usize);
+                    return data.get(offset..(offset + size)).ok_or_else(|| anyhow!("truncated ICC profile"));
+                }
+            }
+            bail!("ICC profile has no {} tag", String::from_utf8_lossy(sig))
+        };
+
+        let mut colorants = [[0.0; 3]; 3];
+        for (col, sig) in [b"rXYZ", b"gXYZ", b"bXYZ"].iter().enumerate() {
+            let xyz = tag(sig)?;
+            for (row, colorant) in colorants.iter_mut().enumerate() {
+                colorant[col] = fixed_at(xyz, 8 + (row * 4))?;
+            }
+        }
+        let matrix = std::array::from_fn(|row| std::array::from_fn(|col| (0..3).map(|k| XYZ_TO_SRGB[row][k] * colorants[k][col]).sum()));
+        let curves = [curve(tag(b"rTRC")?)?, curve(tag(b"gTRC")?)?, curve(tag(b"bTRC")?)?];
+        Ok(Self { curves, matrix })
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        let identity = (0..3).all(|row| (0..3).all(|col| (self.matrix[row][col] - if row == col { 1.0 } else { 0.0 }).abs() < TOLERANCE));
+        identity && self.curves.iter().all(|curve| (0..256).all(|i| (curve[i] - to_linear(i as u8)).abs() < TOLERANCE))
+    }
+
+    pub fn convert(&self, pix: Pixel) -> Pixel {
+        let rgb = [self.curves[0][pix.r as usize], self.curves[1][pix.g as usize], self.curves[2][pix.b as usize]];
+        let [r, g, b] = self.matrix.map(|row| from_linear((row[0] * rgb[0]) + (row[1] * rgb[1]) + (row[2] * rgb[2])));
+        Pixel { r, g, b, a: pix.a }
+    }
+}
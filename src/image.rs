@@ -1,35 +1,1015 @@
-use std::io::{BufRead, Read, Seek, Write};
+use std::io::{BufRead, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use std::fmt::Write as _;
 
 use crossterm::{
-    terminal,
     cursor,
-    style::{self, Stylize, Color},
+    style::{self, Stylize, Color, StyledContent, SetForegroundColor, SetBackgroundColor, ResetColor},
     queue
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{base64, color, exif, quantize, sixel};
+use crate::color::{from_linear, to_linear};
+#[cfg(feature = "dicom")]
+use crate::dicom;
+#[cfg(feature = "icc")]
+use crate::icc;
 
 const PIXEL_CHAR: char = '▀';
+const LOWER_PIXEL_CHAR: char = '▄';
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+const BRAILLE_DOTS: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+const BRAILLE_THRESHOLD: u8 = 128;
+const HALF_BLOCK_MERGE_DISTANCE: i32 = 48;
+const KITTY_CHUNK_SIZE: usize = 4096;
+const DEFAULT_CELL_SIZE: (usize, usize) = (10, 20);
+pub const DEFAULT_CELL_ASPECT: f32 = 2.0;
+pub const DEFAULT_TERMINAL_SIZE: (usize, usize) = (80, 24);
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DIFF_DIM: u8 = 4;
+const DIFF_CHANGED: Pixel = Pixel { r: 255, g: 0, b: 0, a: 255 };
+const MAX_SIZE: f32 = 16_777_216.0;
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Pixel {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub a: u8,
 }
 
-pub struct Image {
+impl Default for Pixel {
+    fn default() -> Self {
+        Self { r: 0, g: 0, b: 0, a: 255 }
+    }
+}
+
+impl Pixel {
+    pub fn luminance(&self) -> u8 {
+        ((0.299 * self.r as f32) + (0.587 * self.g as f32) + (0.114 * self.b as f32)).round() as u8
+    }
+}
+
+pub const DEFAULT_MAX_PIXELS: u64 = 100_000_000;
+const MAX_BYTES_PER_PIXEL: u64 = 16;
+const MAX_AREA_SAMPLES: usize = 16;
+const CHECKER_SIZE: usize = 8;
+const CHECKER_LIGHT: u8 = 204;
+const CHECKER_DARK: u8 = 153;
+const GRID_MIN_ZOOM: f32 = 4.0;
+const GRID_SHADE: u8 = 48;
+const BLACK: Pixel = Pixel { r: 0, g: 0, b: 0, a: 255 };
+const WHITE: Pixel = Pixel { r: 255, g: 255, b: 255, a: 255 };
+const MAGENTA: Pixel = Pixel { r: 255, g: 0, b: 255, a: 255 };
+pub const DEFAULT_THRESHOLD: u8 = 128;
+pub const DEFAULT_SHARPEN: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Background {
+    Color(Pixel),
+    Checkerboard,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Color(Pixel::default())
+    }
+}
+
+impl Background {
+    fn at(&self, x: usize, y: usize) -> Pixel {
+        match self {
+            Self::Color(pix) => *pix,
+            Self::Checkerboard => {
+                let v = if ((x / CHECKER_SIZE) + (y / CHECKER_SIZE)).is_multiple_of(2) { CHECKER_LIGHT } else { CHECKER_DARK };
+                Pixel { r: v, g: v, b: v, a: 255 }
+            },
+        }
+    }
+
+    fn composite(&self, pix: Pixel, x: usize, y: usize) -> Pixel {
+        if pix.a == 255 {
+            return pix;
+        }
+        let bg = self.at(x, y);
+        Pixel {
+            r: apply_alpha(pix.r, pix.a, bg.r),
+            g: apply_alpha(pix.g, pix.a, bg.g),
+            b: apply_alpha(pix.b, pix.a, bg.b),
+            a: 255,
+        }
+    }
+
+    pub fn padding(&self) -> Pixel {
+        match self {
+            Self::Color(pix) => *pix,
+            Self::Checkerboard => Pixel::default(),
+        }
+    }
+}
+
+impl std::fmt::Display for Pixel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl std::str::FromStr for Pixel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("invalid color: {}", s);
+        }
+        Ok(Self {
+            r: u8::from_str_radix(&hex[0..2], 16)?,
+            g: u8::from_str_radix(&hex[2..4], 16)?,
+            b: u8::from_str_radix(&hex[4..6], 16)?,
+            a: 255,
+        })
+    }
+}
+
+impl std::str::FromStr for Background {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "checkerboard" {
+            Ok(Self::Checkerboard)
+        } else {
+            Ok(Self::Color(s.parse()?))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Crop {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl std::str::FromStr for Crop {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s.split(',').map(|p| p.trim().parse::<usize>()).collect::<std::result::Result<Vec<_>, _>>();
+        match parts.as_deref() {
+            Ok([x, y, width, height]) => Ok(Self { x: *x, y: *y, width: *width, height: *height }),
+            _ => bail!("invalid crop (expected X,Y,W,H): {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RawFormat {
+    Gray8,
+    GrayAlpha8,
+    Rgb8,
+    Rgba8,
+    Gray16,
+    Rgb16,
+    Rgba16,
+}
+
+impl RawFormat {
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Gray8 => 1,
+            Self::GrayAlpha8 | Self::Gray16 => 2,
+            Self::Rgb8 => 3,
+            Self::Rgba8 => 4,
+            Self::Rgb16 => 6,
+            Self::Rgba16 => 8,
+        }
+    }
+}
+
+impl std::str::FromStr for RawFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gray8" | "l8" => Ok(Self::Gray8),
+            "grayalpha8" | "la8" => Ok(Self::GrayAlpha8),
+            "rgb8" => Ok(Self::Rgb8),
+            "rgba8" => Ok(Self::Rgba8),
+            "gray16" | "l16" => Ok(Self::Gray16),
+            "rgb16" => Ok(Self::Rgb16),
+            "rgba16" => Ok(Self::Rgba16),
+            _ => bail!("unknown raw pixel format: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Raw {
+    pub width: u32,
+    pub height: u32,
+    pub format: RawFormat,
+}
+
+impl std::str::FromStr for Raw {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parsed = s.split_once(':').and_then(|(size, format)| {
+            let (width, height) = size.split_once('x')?;
+            Some((width.trim().parse().ok()?, height.trim().parse().ok()?, format.trim().parse()))
+        });
+        match parsed {
+            Some((width, height, format)) if width > 0 && height > 0 => Ok(Self { width, height, format: format? }),
+            _ => bail!("invalid raw layout (expected WxH:FORMAT): {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum ToneMap {
+    #[default]
+    Clamp,
+    Normalize,
+    Reinhard,
+}
+
+impl ToneMap {
+    fn apply(self, im: &mut image::DynamicImage) {
+        let (data, channels): (&mut [f32], usize) = match im {
+            image::DynamicImage::ImageRgb32F(im) => (im, 3),
+            image::DynamicImage::ImageRgba32F(im) => (im, 4),
+            _ => return,
+        };
+        match self {
+            Self::Clamp => {},
+            Self::Normalize => {
+                let (min, max) = data.chunks(channels)
+                    .flat_map(|pix| &pix[..3])
+                    .filter(|v| v.is_finite())
+                    .fold((f32::MAX, f32::MIN), |(min, max), &v| (min.min(v), max.max(v)));
+                if max > min {
+                    for pix in data.chunks_mut(channels) {
+                        for v in &mut pix[..3] {
+                            *v = (*v - min) / (max - min);
+                        }
+                    }
+                }
+            },
+            Self::Reinhard => {
+                for pix in data.chunks_mut(channels) {
+                    for v in &mut pix[..3] {
+                        let x = v.max(0.0);
+                        *v = x / (1.0 + x);
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for ToneMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "clamp" => Ok(Self::Clamp),
+            "normalize" => Ok(Self::Normalize),
+            "reinhard" => Ok(Self::Reinhard),
+            _ => bail!("unknown tone map: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub crop: Option<Crop>,
+    pub cell_aspect: Option<f32>,
+    pub linear: bool,
+    pub sampling: Sampling,
+    pub tone_map: ToneMap,
+    pub target: Option<(usize, usize)>,
+    pub max_pixels: u64,
+    pub trim: Option<u8>,
+    pub rotate: u8,
+    pub raw: Option<Raw>,
+    pub dicom: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            crop: None,
+            cell_aspect: None,
+            linear: false,
+            sampling: Sampling::default(),
+            tone_map: ToneMap::default(),
+            target: None,
+            max_pixels: DEFAULT_MAX_PIXELS,
+            trim: None,
+            rotate: 0,
+            raw: None,
+            dicom: false,
+        }
+    }
+}
+
+impl LoadOptions {
+    fn limits(&self) -> image::io::Limits {
+        let mut limits = image::io::Limits::no_limits();
+        if self.max_pixels > 0 {
+            limits.max_alloc = Some(self.max_pixels.saturating_mul(MAX_BYTES_PER_PIXEL));
+        }
+        limits
+    }
+
+    fn check_dimensions(&self, (width, height): (u32, u32)) -> Result<()> {
+        let pixels = width as u64 * height as u64;
+        if self.max_pixels > 0 && pixels > self.max_pixels {
+            bail!("image is {}x{} ({} pixels), which exceeds the limit of {} pixels", width, height, pixels, self.max_pixels);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+struct Orientation {
+    turns: u8,
+    flipped: bool,
+}
+
+impl Orientation {
+    fn from_exif(value: u16) -> Self {
+        let (turns, flipped) = match value {
+            2 => (0, true),
+            3 => (2, false),
+            4 => (2, true),
+            5 => (1, true),
+            6 => (1, false),
+            7 => (3, true),
+            8 => (3, false),
+            _ => (0, false),
+        };
+        Self { turns, flipped }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Sampling {
+    #[default]
+    Nearest,
+    Bilinear,
+    Area,
+}
+
+impl std::str::FromStr for Sampling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "bilinear" => Ok(Self::Bilinear),
+            "area" => Ok(Self::Area),
+            _ => bail!("unknown sampling: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Simulation {
+    #[default]
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl Simulation {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Protanopia,
+            Self::Protanopia => Self::Deuteranopia,
+            Self::Deuteranopia => Self::Tritanopia,
+            Self::Tritanopia => Self::None,
+        }
+    }
+
+    fn matrix(self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            Self::None => None,
+            Self::Protanopia => Some([
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ]),
+            Self::Deuteranopia => Some([
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ]),
+            Self::Tritanopia => Some([
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ]),
+        }
+    }
+
+    fn apply(self, pix: Pixel) -> Pixel {
+        let Some(m) = self.matrix() else {
+            return pix;
+        };
+        let rgb = [to_linear(pix.r), to_linear(pix.g), to_linear(pix.b)];
+        let [r, g, b] = m.map(|row| from_linear(row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]));
+        Pixel { r, g, b, a: pix.a }
+    }
+}
+
+impl std::str::FromStr for Simulation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "protanopia" => Ok(Self::Protanopia),
+            "deuteranopia" => Ok(Self::Deuteranopia),
+            "tritanopia" => Ok(Self::Tritanopia),
+            _ => bail!("unknown simulation: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Protocol {
+    #[default]
+    Blocks,
+    Kitty,
+    Sixel,
+    Iterm2,
+}
+
+impl Protocol {
+    pub fn detect() -> Self {
+        let term = std::env::var("TERM").unwrap_or_default();
+        let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+        if std::env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || term_program == "WezTerm" {
+            Self::Kitty
+        } else if term_program == "iTerm.app" {
+            Self::Iterm2
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") || term.starts_with("yaft") {
+            Self::Sixel
+        } else {
+            Self::Blocks
+        }
+    }
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "blocks" => Ok(Self::Blocks),
+            "kitty" => Ok(Self::Kitty),
+            "sixel" => Ok(Self::Sixel),
+            "iterm2" => Ok(Self::Iterm2),
+            _ => bail!("unknown protocol: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Charset {
+    #[default]
+    HalfBlock,
+    Sextant,
+    Ascii,
+    Braille,
+}
+
+impl Charset {
+    fn cell_size(self) -> (usize, usize) {
+        match self {
+            Self::HalfBlock | Self::Ascii => (1, 2),
+            Self::Sextant => (2, 3),
+            Self::Braille => (2, 4),
+        }
+    }
+
+    pub fn is_text(self) -> bool {
+        matches!(self, Self::Ascii | Self::Braille)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Glyph {
+    #[default]
+    Auto,
+    Upper,
+    Lower,
+}
+
+impl std::str::FromStr for Glyph {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "upper" => Ok(Self::Upper),
+            "lower" => Ok(Self::Lower),
+            _ => bail!("unknown glyph: {}", s),
+        }
+    }
+}
+
+impl std::str::FromStr for Charset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "half" | "halfblock" => Ok(Self::HalfBlock),
+            "sextant" => Ok(Self::Sextant),
+            "ascii" => Ok(Self::Ascii),
+            "braille" => Ok(Self::Braille),
+            _ => bail!("unknown charset: {}", s),
+        }
+    }
+}
+
+pub fn usable_size(cols: usize, rows: usize) -> (usize, usize) {
+    if cols == 0 || rows == 0 {
+        DEFAULT_TERMINAL_SIZE
+    } else {
+        (cols, rows)
+    }
+}
+
+pub fn terminal_size() -> Result<(usize, usize)> {
+    let ws = crossterm::terminal::window_size()?;
+    Ok(usable_size(ws.columns as usize, ws.rows as usize))
+}
+
+fn cell_pixels() -> (usize, usize) {
+    match crossterm::terminal::window_size() {
+        Ok(ws) if ws.width > 0 && ws.height > 0 && ws.columns > 0 && ws.rows > 0 => {
+            ((ws.width as usize / ws.columns as usize).max(1), (ws.height as usize / ws.rows as usize).max(1))
+        },
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+pub fn detect_cell_aspect() -> f32 {
+    match crossterm::terminal::window_size() {
+        Ok(ws) if ws.width > 0 && ws.height > 0 && ws.columns > 0 && ws.rows > 0 => {
+            (ws.height as f32 / ws.rows as f32) / (ws.width as f32 / ws.columns as f32)
+        },
+        _ => DEFAULT_CELL_ASPECT,
+    }
+}
+
+struct Frame {
     pixels: Vec<Pixel>,
+    delay: Duration,
+}
+
+pub struct Image {
+    frames: Vec<Frame>,
+    frame: usize,
+    loop_count: Option<u16>,
+    loops: u32,
     width: usize,
     height: usize,
+    format: Option<image::ImageFormat>,
+    crop: Option<Crop>,
+    orientation: Orientation,
+    sampling: Sampling,
+    cell_aspect: f32,
+    linear: bool,
+    data: Vec<u8>,
+    #[cfg(feature = "dicom")]
+    dicom: Option<Box<dicom::Dicom>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    pub colors: color::Colors,
+    pub ansi16: [Pixel; 16],
+    pub reserved_rows: usize,
+    pub grayscale: bool,
+    pub invert: bool,
+    pub magenta: bool,
+    pub padding: Pixel,
+    pub background: Background,
+    pub charset: Charset,
+    pub glyph: Glyph,
+    pub keep_color: bool,
+    pub brightness: i16,
+    pub contrast: f32,
+    pub simulation: Simulation,
+    pub threshold: Option<u8>,
+    pub grid: bool,
+    pub sharpen: Option<f32>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            colors: color::Colors::default(),
+            ansi16: color::ANSI16,
+            reserved_rows: 0,
+            grayscale: false,
+            invert: false,
+            magenta: false,
+            padding: Pixel::default(),
+            background: Background::default(),
+            charset: Charset::default(),
+            glyph: Glyph::default(),
+            keep_color: false,
+            brightness: 0,
+            contrast: 1.0,
+            simulation: Simulation::None,
+            threshold: None,
+            grid: false,
+            sharpen: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    fn is_identity(&self) -> bool {
+        !self.grayscale && !self.invert && !self.magenta && self.background == Background::default() && self.brightness == 0 && self.contrast == 1.0 && self.simulation == Simulation::None && self.threshold.is_none() && self.sharpen.is_none()
+    }
+
+    fn adjust(&self, value: u8) -> u8 {
+        let value = ((value as f32 - 128.0) * self.contrast) + 128.0 + self.brightness as f32;
+        value.round().clamp(0.0, 255.0) as u8
+    }
+
+    fn apply(&self, pix: Pixel) -> Pixel {
+        let pix = if self.brightness != 0 || self.contrast != 1.0 {
+            Pixel {
+                r: self.adjust(pix.r),
+                g: self.adjust(pix.g),
+                b: self.adjust(pix.b),
+                a: pix.a,
+            }
+        } else {
+            pix
+        };
+
+        let pix = self.simulation.apply(pix);
+
+        let pix = match self.threshold {
+            Some(threshold) => Pixel { a: pix.a, ..if pix.luminance() >= threshold { WHITE } else { BLACK } },
+            None => pix,
+        };
+
+        let pix = if self.grayscale {
+            let l = pix.luminance();
+            Pixel { r: l, g: l, b: l, a: pix.a }
+        } else {
+            pix
+        };
+
+        if self.invert {
+            Pixel { r: 255 - pix.r, g: 255 - pix.g, b: 255 - pix.b, a: pix.a }
+        } else {
+            pix
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RenderKey {
+    cols: usize,
+    rows: usize,
+    pos: (usize, usize),
+    offset: (usize, usize),
+    zoom: (f32, f32),
+    sampling: Sampling,
+    cell_aspect: f32,
+    linear: bool,
+    crop: Option<Crop>,
+    orientation: Orientation,
+    frame: usize,
+    options: RenderOptions,
+}
+
+#[derive(Default)]
+pub struct RenderCache {
+    key: Option<RenderKey>,
+    pixels: Vec<Pixel>,
+    indices: Vec<u8>,
+    frame_size: Option<(usize, usize)>,
+    frame: Vec<StyledContent<char>>,
+    image_key: Option<RenderKey>,
+    overlays: Vec<(usize, usize, usize, usize)>,
+    origin: (usize, usize),
+}
+
+impl RenderCache {
+    fn update(&mut self, im: &Image, key: RenderKey) {
+        if self.key == Some(key) {
+            return;
+        }
+
+        let (sx, sy) = key.options.charset.cell_size();
+        let (width, height) = (key.cols * sx, key.rows * sy);
+        self.pixels = collect(width * height, |i| {
+            let (u, v) = (i % width, i / width);
+            let (x, y) = (u / sx, ((v / sy) * 2) + (((v % sy) * 2) / sy));
+            if x < key.offset.0 || y < key.offset.1 {
+                key.options.padding
+            } else {
+                let shift = ((key.pos.1 as isize - key.offset.1 as isize) * sy as isize).div_euclid(2);
+                let sub = (v as isize + shift).max(0) as usize;
+                let pos = (((x - key.offset.0) + key.pos.0) * sx + (u % sx), sub);
+                if !im.contains(pos, key.zoom, (sx, sy)) {
+                    return key.options.padding;
+                }
+                let pix = key.options.apply(im.subpixel(pos, key.zoom, (sx, sy)));
+                let pix = if key.options.magenta && pix.a == 0 { MAGENTA } else { im.composite(&key.options.background, pix, pos, key.zoom, (sx, sy)) };
+                if key.options.grid && key.zoom.0.min(key.zoom.1) >= GRID_MIN_ZOOM && im.on_grid(pos, key.zoom, (sx, sy)) {
+                    grid_line(pix)
+                } else {
+                    pix
+                }
+            }
+        });
+        if let Some(amount) = key.options.sharpen {
+            self.pixels = sharpen(&self.pixels, width, height, amount);
+        }
+        self.indices = match (key.options.colors, key.options.charset) {
+            (color::Colors::Ansi256, Charset::HalfBlock) => color::dither(&self.pixels, width, height),
+            _ => Vec::new(),
+        };
+        self.key = Some(key);
+    }
+
+    pub fn force_full(&mut self) {
+        self.frame_size = None;
+        self.image_key = None;
+    }
+
+    pub fn set_overlays(&mut self, overlays: Vec<(usize, usize, usize, usize)>) {
+        if self.overlays != overlays {
+            self.overlays = overlays;
+            self.force_full();
+        }
+    }
+
+    pub fn set_origin(&mut self, origin: (usize, usize)) {
+        if self.origin != origin {
+            self.origin = origin;
+            self.force_full();
+        }
+    }
+
+    fn in_overlay(&self, x: usize, y: usize) -> bool {
+        self.overlays.iter().any(|&(ox, oy, w, h)| x >= ox && x < ox + w && y >= oy && y < oy + h)
+    }
+
+    fn begin_image(&mut self, key: RenderKey) -> bool {
+        if self.image_key == Some(key) {
+            false
+        } else {
+            self.image_key = Some(key);
+            true
+        }
+    }
+
+    fn begin_frame(&mut self, cols: usize, rows: usize) -> bool {
+        if self.frame_size == Some((cols, rows)) {
+            false
+        } else {
+            self.frame_size = Some((cols, rows));
+            self.frame.clear();
+            self.frame.resize(cols * rows, ' '.stylize());
+            true
+        }
+    }
+
+    fn swap_cell(&mut self, x: usize, y: usize, cell: StyledContent<char>) -> bool {
+        let cols = self.frame_size.map(|s| s.0).unwrap_or(0);
+        let prev = &mut self.frame[(y * cols) + x];
+        if *prev == cell {
+            false
+        } else {
+            *prev = cell;
+            true
+        }
+    }
+
+    fn color(&self, x: usize, sy: usize) -> Color {
+        let key = self.key.expect("render cache is populated");
+        let i = (sy * key.cols) + x;
+        match key.options.colors {
+            color::Colors::Ansi256 => Color::AnsiValue(self.indices[i]),
+            _ => to_color(&key.options, self.pixels[i]),
+        }
+    }
+
+    fn lower_glyph(&self, x: usize, y: usize, upper: Pixel, lower: Pixel) -> bool {
+        let key = self.key.expect("render cache is populated");
+        match key.options.glyph {
+            Glyph::Upper => false,
+            Glyph::Lower => true,
+            Glyph::Auto => {
+                let above = (y > 0).then(|| self.pixels[(((y * 2) - 1) * key.cols) + x]);
+                let below = self.pixels.get((((y * 2) + 2) * key.cols) + x).copied();
+                above.map_or(0, |above| distance(lower, above)) > below.map_or(0, |below| distance(upper, below))
+            },
+        }
+    }
+
+    fn glyph(&self, x: usize, y: usize) -> (char, Color, Color) {
+        let key = self.key.expect("render cache is populated");
+        match key.options.charset {
+            Charset::HalfBlock => {
+                let (top, bottom) = (self.color(x, y * 2), self.color(x, (y * 2) + 1));
+                let (upper, lower) = (self.pixels[(y * 2 * key.cols) + x], self.pixels[(((y * 2) + 1) * key.cols) + x]);
+                if top == bottom {
+                    (' ', top, top)
+                } else if key.options.colors == color::Colors::TrueColor && distance(upper, lower) <= HALF_BLOCK_MERGE_DISTANCE {
+                    let color = to_color(&key.options, mean(&[upper, lower], 0b11, true));
+                    (' ', color, color)
+                } else if self.lower_glyph(x, y, upper, lower) {
+                    (LOWER_PIXEL_CHAR, bottom, top)
+                } else {
+                    (PIXEL_CHAR, top, bottom)
+                }
+            },
+            Charset::Sextant => {
+                let width = key.cols * 2;
+                let mut cell = [Pixel::default(); 6];
+                for (i, pix) in cell.iter_mut().enumerate() {
+                    *pix = self.pixels[(((y * 3) + (i / 2)) * width) + (x * 2) + (i % 2)];
+                }
+                let (pattern, fg, bg) = split(&cell);
+                (sextant(pattern), to_color(&key.options, fg), to_color(&key.options, bg))
+            },
+            Charset::Ascii => {
+                let cell = [self.pixels[(y * 2 * key.cols) + x], self.pixels[(((y * 2) + 1) * key.cols) + x]];
+                let l = (cell[0].luminance() as usize + cell[1].luminance() as usize) / 2;
+                let ch = ASCII_RAMP[(l * (ASCII_RAMP.len() - 1)) / 255] as char;
+                (ch, self.text_color(&cell, 0b11), Color::Reset)
+            },
+            Charset::Braille => {
+                let width = key.cols * 2;
+                let mut cell = [Pixel::default(); 8];
+                for (i, pix) in cell.iter_mut().enumerate() {
+                    *pix = self.pixels[(((y * 4) + (i / 2)) * width) + (x * 2) + (i % 2)];
+                }
+                let mut lit = 0u8;
+                let mut dots = 0u8;
+                for (i, pix) in cell.iter().enumerate() {
+                    if pix.luminance() >= BRAILLE_THRESHOLD {
+                        lit |= 1 << i;
+                        dots |= BRAILLE_DOTS[i];
+                    }
+                }
+                let ch = char::from_u32(0x2800 + dots as u32).unwrap_or(' ');
+                (ch, self.text_color(&cell, lit), Color::Reset)
+            },
+        }
+    }
+
+    fn text_color(&self, cell: &[Pixel], lit: u8) -> Color {
+        let key = self.key.expect("render cache is populated");
+        if !key.options.keep_color {
+            Color::Reset
+        } else if lit == 0 {
+            to_color(&key.options, mean(cell, u8::MAX, true))
+        } else {
+            to_color(&key.options, mean(cell, lit, true))
+        }
+    }
+}
+
+const ANSI16_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+fn to_color(options: &RenderOptions, pix: Pixel) -> Color {
+    let rgb = (pix.r as i32, pix.g as i32, pix.b as i32);
+    match options.colors {
+        color::Colors::TrueColor => Color::Rgb { r: pix.r, g: pix.g, b: pix.b },
+        color::Colors::Ansi256 => Color::AnsiValue(color::nearest_ansi256(rgb)),
+        color::Colors::Ansi16 => ANSI16_COLORS[color::nearest_ansi16(pix, &options.ansi16) as usize],
+        color::Colors::Mono => if color::mono(rgb) { Color::White } else { Color::Black },
+    }
 }
 
-fn apply_alpha16(value: u16, alpha: u16) -> u16 {
-    ((value as u32) * (alpha as u32) / 65535) as u16
+fn grid_line(pix: Pixel) -> Pixel {
+    let shade = |c: u8| if pix.luminance() > 127 { c.saturating_sub(GRID_SHADE) } else { c.saturating_add(GRID_SHADE) };
+    Pixel { r: shade(pix.r), g: shade(pix.g), b: shade(pix.b), a: pix.a }
 }
 
-fn apply_alpha(value: u8, alpha: u8) -> u8 {
-    ((value as u32) * (alpha as u32) / 255) as u8
+fn sharpen(pixels: &[Pixel], width: usize, height: usize, amount: f32) -> Vec<Pixel> {
+    collect(width * height, |i| {
+        let (x, y) = (i % width, i / width);
+        let mut sum = [0u32; 3];
+        let mut count = 0;
+        for ny in y.saturating_sub(1)..(y + 2).min(height) {
+            for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                let pix = pixels[ny * width + nx];
+                sum[0] += pix.r as u32;
+                sum[1] += pix.g as u32;
+                sum[2] += pix.b as u32;
+                count += 1;
+            }
+        }
+        let pix = pixels[i];
+        let unsharp = |value: u8, sum: u32| (value as f32 + (value as f32 - sum as f32 / count as f32) * amount).round().clamp(0.0, 255.0) as u8;
+        Pixel { r: unsharp(pix.r, sum[0]), g: unsharp(pix.g, sum[1]), b: unsharp(pix.b, sum[2]), a: pix.a }
+    })
+}
+
+fn distance(a: Pixel, b: Pixel) -> i32 {
+    let (dr, dg, db) = (a.r as i32 - b.r as i32, a.g as i32 - b.g as i32, a.b as i32 - b.b as i32);
+    (dr * dr) + (dg * dg) + (db * db)
+}
+
+fn mean(pixels: &[Pixel], pattern: u8, set: bool) -> Pixel {
+    let (mut r, mut g, mut b, mut n) = (0u32, 0u32, 0u32, 0u32);
+    for (i, pix) in pixels.iter().enumerate() {
+        if ((pattern >> i) & 1 == 1) == set {
+            r += pix.r as u32;
+            g += pix.g as u32;
+            b += pix.b as u32;
+            n += 1;
+        }
+    }
+    if n == 0 {
+        return Pixel::default();
+    }
+    Pixel { r: (r / n) as u8, g: (g / n) as u8, b: (b / n) as u8, a: 255 }
+}
+
+fn split(pixels: &[Pixel]) -> (u8, Pixel, Pixel) {
+    let mut fg = *pixels.iter().max_by_key(|p| p.luminance()).expect("cell has pixels");
+    let mut bg = *pixels.iter().min_by_key(|p| p.luminance()).expect("cell has pixels");
+    if fg == bg {
+        return (0, fg, bg);
+    }
+
+    let mut pattern = 0;
+    for _ in 0..2 {
+        pattern = pixels.iter().enumerate().fold(0, |acc, (i, &pix)| {
+            if distance(pix, fg) < distance(pix, bg) { acc | (1 << i) } else { acc }
+        });
+        fg = mean(pixels, pattern, true);
+        bg = mean(pixels, pattern, false);
+    }
+    (pattern, fg, bg)
+}
+
+fn sextant(pattern: u8) -> char {
+    match pattern {
+        0 => ' ',
+        21 => '▌',
+        42 => '▐',
+        63 => '█',
+        _ => {
+            let index = pattern as u32 - 1 - (pattern > 21) as u32 - (pattern > 42) as u32;
+            char::from_u32(0x1FB00 + index).unwrap_or(PIXEL_CHAR)
+        },
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn collect<T: Send, F: Fn(usize) -> T + Sync + Send>(len: usize, f: F) -> Vec<T> {
+    (0..len).into_par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn collect<T, F: Fn(usize) -> T>(len: usize, f: F) -> Vec<T> {
+    (0..len).map(f).collect()
+}
+
+fn apply_alpha(value: u8, alpha: u8, bg: u8) -> u8 {
+    (((value as u32) * (alpha as u32) + (bg as u32) * (255 - alpha as u32)) / 255) as u8
 }
 
 fn u16_to_u8(value: u16) -> u8 {
@@ -47,7 +1027,157 @@ fn f32_to_u8(value: f32) -> u8 {
     }
 }
 
+fn decode_jpeg_scaled(data: &[u8], (width, height): (usize, usize)) -> Result<Option<image::DynamicImage>> {
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    decoder.read_info()?;
+    let info = decoder.info().expect("info was read");
+    let clamp = |v: usize| v.clamp(1, u16::MAX as usize) as u16;
+    let (w, h) = decoder.scale(clamp(width), clamp(height))?;
+    if (w, h) == (info.width, info.height) {
+        return Ok(None);
+    }
+    let (w, h) = (w as u32, h as u32);
+    Ok(match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => image::GrayImage::from_raw(w, h, decoder.decode()?).map(image::DynamicImage::ImageLuma8),
+        jpeg_decoder::PixelFormat::RGB24 => image::RgbImage::from_raw(w, h, decoder.decode()?).map(image::DynamicImage::ImageRgb8),
+        _ => None,
+    })
+}
+
+fn gif_loop_count(data: &[u8]) -> Option<u16> {
+    const NETSCAPE: &[u8] = b"NETSCAPE2.0";
+    let pos = data.windows(NETSCAPE.len()).position(|w| w == NETSCAPE)? + NETSCAPE.len();
+    match data.get(pos..(pos + 4))? {
+        [3, 1, lo, hi] => Some(u16::from_le_bytes([*lo, *hi])),
+        _ => None,
+    }
+}
+
+fn apng_loop_count(data: &[u8]) -> Option<u16> {
+    let pos = data.windows(4).position(|w| w == b"acTL")? + 4;
+    let plays = u32::from_be_bytes(data.get((pos + 4)..(pos + 8))?.try_into().ok()?);
+    match plays {
+        0 => Some(0),
+        1 => None,
+        n => Some((n - 1).min(u16::MAX as u32) as u16),
+    }
+}
+
+fn is_apng(data: &[u8]) -> bool {
+    image::codecs::png::PngDecoder::new(std::io::Cursor::new(data)).and_then(|decoder| decoder.is_apng()).unwrap_or(false)
+}
+
+fn is_bilevel_png(data: &[u8]) -> bool {
+    data.len() > 25 && &data[12..16] == b"IHDR" && data[24] == 1 && data[25] == 0
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
 impl Image {
+    fn from_pixels(pixels: Vec<Pixel>, width: usize, height: usize) -> Self {
+        Self {
+            frames: vec![Frame { pixels, delay: Duration::ZERO }],
+            frame: 0,
+            loop_count: None,
+            loops: 0,
+            width,
+            height,
+            format: None,
+            crop: None,
+            orientation: Orientation::default(),
+            sampling: Sampling::default(),
+            cell_aspect: DEFAULT_CELL_ASPECT,
+            linear: false,
+            data: Vec::new(),
+            #[cfg(feature = "dicom")]
+            dicom: None,
+        }
+    }
+
+    fn pixels(&self) -> &[Pixel] {
+        &self.frames[self.frame].pixels
+    }
+
+    fn region(&self) -> Crop {
+        self.crop.unwrap_or(Crop { x: 0, y: 0, width: self.width, height: self.height })
+    }
+
+    fn view_size(&self) -> (usize, usize) {
+        let region = self.region();
+        if self.orientation.turns % 2 == 1 {
+            (region.height, region.width)
+        } else {
+            (region.width, region.height)
+        }
+    }
+
+    fn at(&self, x: usize, y: usize) -> Pixel {
+        let region = self.region();
+        let (width, _) = self.view_size();
+        let x = if self.orientation.flipped { width - 1 - x } else { x };
+        let (sx, sy) = match self.orientation.turns {
+            1 => (y, region.height - 1 - x),
+            2 => (region.width - 1 - x, region.height - 1 - y),
+            3 => (region.width - 1 - y, x),
+            _ => (x, y),
+        };
+        self.pixels()[((region.y + sy) * self.width) + region.x + sx]
+    }
+
+    pub fn set_crop(&mut self, crop: Option<Crop>) {
+        self.crop = crop.and_then(|crop| {
+            if self.width == 0 || self.height == 0 {
+                return None;
+            }
+            let x = crop.x.min(self.width - 1);
+            let y = crop.y.min(self.height - 1);
+            Some(Crop {
+                x,
+                y,
+                width: crop.width.clamp(1, self.width - x),
+                height: crop.height.clamp(1, self.height - y),
+            })
+        });
+    }
+
+    pub fn trim(&mut self, threshold: u8) {
+        let region = self.region();
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for frame in &self.frames {
+            for y in region.y..region.y + region.height {
+                let row = &frame.pixels[(y * self.width) + region.x..(y * self.width) + region.x + region.width];
+                let Some(first) = row.iter().position(|pix| pix.a > threshold) else {
+                    continue;
+                };
+                let last = row.iter().rposition(|pix| pix.a > threshold).unwrap_or(first);
+                let (x0, x1) = (region.x + first, region.x + last);
+                bounds = Some(match bounds {
+                    Some((bx0, by0, bx1, by1)) => (bx0.min(x0), by0.min(y), bx1.max(x1), by1.max(y)),
+                    None => (x0, y, x1, y),
+                });
+            }
+        }
+        if let Some((x0, y0, x1, y1)) = bounds {
+            self.crop = Some(Crop { x: x0, y: y0, width: x1 - x0 + 1, height: y1 - y0 + 1 });
+        }
+    }
+
+    pub fn rotate(&mut self, clockwise: bool) {
+        let turn = if clockwise != self.orientation.flipped { 1 } else { 3 };
+        self.orientation.turns = (self.orientation.turns + turn) % 4;
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        self.orientation.flipped = !self.orientation.flipped;
+    }
+
+    pub fn flip_vertical(&mut self) {
+        self.orientation.flipped = !self.orientation.flipped;
+        self.orientation.turns = (self.orientation.turns + 2) % 4;
+    }
+
     fn new_gray8(im: image::GrayImage) -> Result<Self> {
         let (width, height) = im.dimensions();
         let width = width as usize;
@@ -58,13 +1188,16 @@ impl Image {
                 r: pix.0[0],
                 g: pix.0[0],
                 b: pix.0[0],
+                a: 255,
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
+    }
+
+    fn new_bilevel(im: image::GrayImage) -> Result<Self> {
+        let (width, height) = im.dimensions();
+        let pixels = im.into_raw().into_iter().map(|v| if v >= DEFAULT_THRESHOLD { WHITE } else { BLACK }).collect();
+        Ok(Self::from_pixels(pixels, width as usize, height as usize))
     }
 
     fn new_grayalpha8(im: image::GrayAlphaImage) -> Result<Self> {
@@ -73,18 +1206,14 @@ impl Image {
         let height = height as usize;
         let mut pixels = Vec::with_capacity(width * height);
         for pix in im.pixels() {
-            let val = apply_alpha(pix.0[0], pix.0[1]);
             pixels.push(Pixel {
-                r: val,
-                g: val,
-                b: val,
+                r: pix.0[0],
+                g: pix.0[0],
+                b: pix.0[0],
+                a: pix.0[1],
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_rgb8(im: image::RgbImage) -> Result<Self> {
@@ -97,13 +1226,10 @@ impl Image {
                 r: pix.0[0],
                 g: pix.0[1],
                 b: pix.0[2],
+                a: 255,
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_rgba8(im: image::RgbaImage) -> Result<Self> {
@@ -113,16 +1239,13 @@ impl Image {
         let mut pixels = Vec::with_capacity(width * height);
         for pix in im.pixels() {
             pixels.push(Pixel {
-                r: apply_alpha(pix.0[0], pix.0[3]),
-                g: apply_alpha(pix.0[1], pix.0[3]),
-                b: apply_alpha(pix.0[2], pix.0[3]),
+                r: pix.0[0],
+                g: pix.0[1],
+                b: pix.0[2],
+                a: pix.0[3],
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_gray16(im: image::ImageBuffer<image::Luma<u16>, Vec<u16>>) -> Result<Self> {
@@ -136,13 +1259,10 @@ impl Image {
                 r: val,
                 g: val,
                 b: val,
+                a: 255,
             });
-        }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        }
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_grayalpha16(im: image::ImageBuffer<image::LumaA<u16>, Vec<u16>>) -> Result<Self> {
@@ -151,18 +1271,15 @@ impl Image {
         let height = height as usize;
         let mut pixels = Vec::with_capacity(width * height);
         for pix in im.pixels() {
-            let val = u16_to_u8(apply_alpha16(pix.0[0], pix.0[1]));
+            let val = u16_to_u8(pix.0[0]);
             pixels.push(Pixel {
                 r: val,
                 g: val,
                 b: val,
+                a: u16_to_u8(pix.0[1]),
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_rgb16(im: image::ImageBuffer<image::Rgb<u16>, Vec<u16>>) -> Result<Self> {
@@ -175,13 +1292,10 @@ impl Image {
                 r: u16_to_u8(pix.0[0]),
                 g: u16_to_u8(pix.0[1]),
                 b: u16_to_u8(pix.0[2]),
+                a: 255,
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_rgba16(im: image::ImageBuffer<image::Rgba<u16>, Vec<u16>>) -> Result<Self> {
@@ -191,16 +1305,13 @@ impl Image {
         let mut pixels = Vec::with_capacity(width * height);
         for pix in im.pixels() {
             pixels.push(Pixel {
-                r: u16_to_u8(apply_alpha16(pix.0[0], pix.0[3])),
-                g: u16_to_u8(apply_alpha16(pix.0[1], pix.0[3])),
-                b: u16_to_u8(apply_alpha16(pix.0[2], pix.0[3])),
+                r: u16_to_u8(pix.0[0]),
+                g: u16_to_u8(pix.0[1]),
+                b: u16_to_u8(pix.0[2]),
+                a: u16_to_u8(pix.0[3]),
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_rgb32f(im: image::Rgb32FImage) -> Result<Self> {
@@ -213,13 +1324,10 @@ impl Image {
                 r: f32_to_u8(pix.0[0]),
                 g: f32_to_u8(pix.0[1]),
                 b: f32_to_u8(pix.0[2]),
+                a: 255,
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
     fn new_rgba32f(im: image::Rgba32FImage) -> Result<Self> {
@@ -229,19 +1337,16 @@ impl Image {
         let mut pixels = Vec::with_capacity(width * height);
         for pix in im.pixels() {
             pixels.push(Pixel {
-                r: f32_to_u8(pix.0[0] * pix.0[3]),
-                g: f32_to_u8(pix.0[1] * pix.0[3]),
-                b: f32_to_u8(pix.0[2] * pix.0[3]),
+                r: f32_to_u8(pix.0[0]),
+                g: f32_to_u8(pix.0[1]),
+                b: f32_to_u8(pix.0[2]),
+                a: f32_to_u8(pix.0[3]),
             });
         }
-        Ok(Self {
-            pixels,
-            width,
-            height,
-        })
+        Ok(Self::from_pixels(pixels, width, height))
     }
 
-    fn new(im: image::DynamicImage) -> Result<Self> {
+    pub fn new(im: image::DynamicImage) -> Result<Self> {
         match im {
             image::DynamicImage::ImageLuma8(im) => {
                 Self::new_gray8(im)
@@ -273,53 +1378,828 @@ impl Image {
             image::DynamicImage::ImageRgba32F(im) => {
                 Self::new_rgba32f(im)
             },
-            _ => {
-                todo!()
+            im => {
+                Self::new_rgba8(im.to_rgba8())
+            },
+        }
+    }
+
+    fn new_animation(decoded: image::Frames, format: image::ImageFormat, loop_count: Option<u16>) -> Result<Self> {
+        let mut frames = Vec::new();
+        let mut size = (0, 0);
+        for frame in decoded {
+            let frame = frame?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = Duration::from_micros((numer as u64 * 1000) / (denom.max(1) as u64));
+            let im = Self::new_rgba8(frame.into_buffer())?;
+            size = (im.width, im.height);
+            frames.extend(im.frames.into_iter().map(|f| Frame { pixels: f.pixels, delay }));
+        }
+
+        if frames.is_empty() {
+            bail!("{:?} contains no frames", format);
+        }
+
+        Ok(Self {
+            frames,
+            frame: 0,
+            loop_count,
+            loops: 0,
+            width: size.0,
+            height: size.1,
+            format: Some(format),
+            crop: None,
+            orientation: Orientation::default(),
+            sampling: Sampling::default(),
+            cell_aspect: DEFAULT_CELL_ASPECT,
+            linear: false,
+            data: Vec::new(),
+            #[cfg(feature = "dicom")]
+            dicom: None,
+        })
+    }
+
+    fn new_gif(data: &[u8], options: &LoadOptions) -> Result<Self> {
+        use image::{AnimationDecoder, ImageDecoder};
+
+        let mut decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(data))?;
+        options.check_dimensions(decoder.dimensions())?;
+        decoder.set_limits(options.limits())?;
+        Self::new_animation(decoder.into_frames(), image::ImageFormat::Gif, gif_loop_count(data))
+    }
+
+    fn new_apng(data: &[u8], options: &LoadOptions) -> Result<Self> {
+        use image::{AnimationDecoder, ImageDecoder};
+
+        let mut decoder = image::codecs::png::PngDecoder::new(std::io::Cursor::new(data))?;
+        options.check_dimensions(decoder.dimensions())?;
+        decoder.set_limits(options.limits())?;
+        Self::new_animation(decoder.apng()?.into_frames(), image::ImageFormat::Png, apng_loop_count(data))
+    }
+
+    fn new_raw(data: &[u8], raw: Raw, options: &LoadOptions) -> Result<Self> {
+        options.check_dimensions((raw.width, raw.height))?;
+        let expected = raw.width as usize * raw.height as usize * raw.format.bytes_per_pixel();
+        if data.len() != expected {
+            bail!("raw data is {} bytes, but {}x{} {} needs {}", data.len(), raw.width, raw.height, format!("{:?}", raw.format).to_lowercase(), expected);
+        }
+        let (width, height) = (raw.width, raw.height);
+        let words = || data.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect::<Vec<_>>();
+        let im = match raw.format {
+            RawFormat::Gray8 => image::GrayImage::from_raw(width, height, data.to_vec()).map(image::DynamicImage::ImageLuma8),
+            RawFormat::GrayAlpha8 => image::GrayAlphaImage::from_raw(width, height, data.to_vec()).map(image::DynamicImage::ImageLumaA8),
+            RawFormat::Rgb8 => image::RgbImage::from_raw(width, height, data.to_vec()).map(image::DynamicImage::ImageRgb8),
+            RawFormat::Rgba8 => image::RgbaImage::from_raw(width, height, data.to_vec()).map(image::DynamicImage::ImageRgba8),
+            RawFormat::Gray16 => image::ImageBuffer::from_raw(width, height, words()).map(image::DynamicImage::ImageLuma16),
+            RawFormat::Rgb16 => image::ImageBuffer::from_raw(width, height, words()).map(image::DynamicImage::ImageRgb16),
+            RawFormat::Rgba16 => image::ImageBuffer::from_raw(width, height, words()).map(image::DynamicImage::ImageRgba16),
+        };
+        Self::new(im.ok_or_else(|| anyhow!("raw data does not match {}x{}", width, height))?)
+    }
+
+    #[cfg(feature = "dicom")]
+    fn new_dicom(data: &[u8], options: &LoadOptions) -> Result<Self> {
+        let dicom = dicom::Dicom::parse(data)?;
+        let (width, height) = dicom.dimensions();
+        options.check_dimensions((width as u32, height as u32))?;
+        let mut im = Self::from_pixels(Vec::new(), width, height);
+        im.dicom = Some(Box::new(dicom));
+        im.render_dicom();
+        Ok(im)
+    }
+
+    #[cfg(feature = "dicom")]
+    fn render_dicom(&mut self) {
+        if let Some(dicom) = &self.dicom {
+            self.frames[0].pixels = dicom.render().into_iter().map(|v| Pixel { r: v, g: v, b: v, a: 255 }).collect();
+        }
+    }
+
+    #[cfg(feature = "dicom")]
+    pub fn window(&self) -> Option<(f32, f32)> {
+        self.dicom.as_ref().map(|dicom| (dicom.window().center, dicom.window().width))
+    }
+
+    #[cfg(not(feature = "dicom"))]
+    pub fn window(&self) -> Option<(f32, f32)> {
+        None
+    }
+
+    #[cfg(feature = "dicom")]
+    pub fn set_window(&mut self, (center, width): (f32, f32)) {
+        if let Some(dicom) = &mut self.dicom {
+            dicom.set_window(dicom::Window { center, width });
+            self.render_dicom();
+        }
+    }
+
+    #[cfg(not(feature = "dicom"))]
+    pub fn set_window(&mut self, _: (f32, f32)) {}
+
+    fn from_reader<R: BufRead + Seek>(reader: image::io::Reader<R>, options: &LoadOptions) -> Result<Self> {
+        let format = reader.format();
+        let mut data = Vec::new();
+        let mut inner = reader.into_inner();
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_to_end(&mut data)?;
+
+        let mut orientation = Orientation::default();
+        let mut bilevel = false;
+        let mut im = match format {
+            _ if options.raw.is_some() => Self::new_raw(&data, options.raw.expect("raw layout is set"), options)?,
+            #[cfg(feature = "dicom")]
+            _ if options.dicom || (format.is_none() && dicom::is_dicom(&data)) => Self::new_dicom(&data, options)?,
+            #[cfg(not(feature = "dicom"))]
+            _ if options.dicom => bail!("DICOM support is not enabled"),
+            Some(image::ImageFormat::Gif) => Self::new_gif(&data, options)?,
+            Some(image::ImageFormat::Png) if is_apng(&data) => Self::new_apng(&data, options)?,
+            Some(fmt) => {
+                if matches!(fmt, image::ImageFormat::Jpeg | image::ImageFormat::Tiff) {
+                    if let Some(value) = exif::orientation(&data) {
+                        orientation = Orientation::from_exif(value);
+                    }
+                }
+                options.check_dimensions(image::io::Reader::with_format(std::io::Cursor::new(&data), fmt).into_dimensions()?)?;
+                let target = options.target.filter(|_| fmt == image::ImageFormat::Jpeg && options.crop.is_none());
+                let target = target.map(|(w, h)| if (orientation.turns + options.rotate) % 2 == 1 { (h, w) } else { (w, h) });
+                let mut decoded = match target.map(|target| decode_jpeg_scaled(&data, target)).transpose()?.flatten() {
+                    Some(decoded) => decoded,
+                    None => {
+                        let mut reader = image::io::Reader::with_format(std::io::Cursor::new(&data), fmt);
+                        reader.limits(options.limits());
+                        use image::ImageDecoder;
+                        let decoder = reader.into_decoder()?;
+                        bilevel = decoder.original_color_type() == image::ExtendedColorType::L1 || (fmt == image::ImageFormat::Png && is_bilevel_png(&data));
+                        image::DynamicImage::from_decoder(decoder)?
+                    },
+                };
+                if bilevel {
+                    Self::new_bilevel(decoded.into_luma8())?
+                } else {
+                    options.tone_map.apply(&mut decoded);
+                    let mut im = Self::new(decoded)?;
+                    im.convert_profile(&data, fmt);
+                    im
+                }
             },
+            None => bail!("unrecognized image format"),
+        };
+        im.format = format;
+        im.orientation = orientation;
+        for _ in 0..options.rotate {
+            im.rotate(true);
+        }
+        if options.raw.is_none() && !options.dicom && matches!(format, Some(image::ImageFormat::Png | image::ImageFormat::Jpeg | image::ImageFormat::Gif)) {
+            im.data = data;
+        }
+        im.set_crop(options.crop);
+        if let Some(threshold) = options.trim {
+            im.trim(threshold);
+        }
+        if let Some(aspect) = options.cell_aspect {
+            im.set_cell_aspect(aspect);
+        }
+        im.linear = options.linear;
+        im.sampling = options.sampling;
+        Ok(im)
+    }
+
+    #[cfg(feature = "icc")]
+    fn convert_profile(&mut self, data: &[u8], format: image::ImageFormat) {
+        use image::ImageDecoder;
+        let profile = image::io::Reader::with_format(std::io::Cursor::new(data), format)
+            .into_decoder()
+            .ok()
+            .and_then(|mut decoder| decoder.icc_profile().ok().flatten())
+            .and_then(|profile| icc::Profile::parse(&profile).ok());
+        if let Some(profile) = profile.filter(|profile| !profile.is_srgb()) {
+            for frame in &mut self.frames {
+                frame.pixels.iter_mut().for_each(|pix| *pix = profile.convert(*pix));
+            }
+        }
+    }
+
+    #[cfg(not(feature = "icc"))]
+    fn convert_profile(&mut self, _: &[u8], _: image::ImageFormat) {}
+
+    pub fn load<R: BufRead + Seek>(im: R, options: &LoadOptions) -> Result<Self> {
+        Self::from_reader(image::io::Reader::new(im).with_guessed_format()?, options)
+    }
+
+    pub fn open<P: AsRef<std::path::Path>>(path: P, options: &LoadOptions) -> Result<Self> {
+        Self::from_reader(image::io::Reader::open(path)?.with_guessed_format()?, options)
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn format(&self) -> Option<image::ImageFormat> {
+        self.format
+    }
+
+    pub fn format_name(&self) -> String {
+        match self.format {
+            Some(format) => format!("{:?}", format).to_uppercase(),
+            None if self.window().is_some() => "DICOM".into(),
+            None => "?".into(),
+        }
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn frame_delay(&self) -> Duration {
+        let delay = self.frames[self.frame].delay;
+        if delay < MIN_FRAME_DELAY {
+            DEFAULT_FRAME_DELAY
+        } else {
+            delay
+        }
+    }
+
+    pub fn advance(&mut self) -> bool {
+        if self.frame + 1 < self.frames.len() {
+            self.frame += 1;
+            return true;
         }
+
+        let more = match self.loop_count {
+            Some(0) => true,
+            Some(n) => self.loops < n as u32,
+            None => false,
+        };
+        if more && self.frames.len() > 1 {
+            self.loops += 1;
+            self.frame = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn reset_loops(&mut self) {
+        self.loops = 0;
     }
 
-    pub fn load<R: BufRead + Seek>(im: R) -> Result<Self> {
-        Self::new(image::io::Reader::new(im).with_guessed_format()?.decode()?)
+    pub fn step(&mut self, forward: bool) {
+        let count = self.frames.len();
+        self.frame = if forward {
+            (self.frame + 1) % count
+        } else {
+            (self.frame + count - 1) % count
+        };
     }
 
-    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
-        Self::new(image::io::Reader::open(path)?.decode()?)
+    pub fn draw_terminal<W: Write>(&self, term: &mut W, cache: &mut RenderCache, options: &RenderOptions, pos: (usize, usize), zoom: (f32, f32)) -> Result<()> {
+        let (cols, rows) = terminal_size()?;
+        self.draw(term, cache, options, (cols, rows.saturating_sub(options.reserved_rows)), pos, zoom)
     }
 
-    pub fn draw<W: Write>(&self, term: &mut W, pos: (usize, usize), offset: (usize, usize), zoom: f32) -> Result<()> {
-        let ws = crossterm::terminal::window_size()?;
-        let twidth = ws.columns as usize;
-        let theight = ws.rows as usize;
+    pub fn draw<W: Write>(&self, term: &mut W, cache: &mut RenderCache, options: &RenderOptions, (cols, rows): (usize, usize), pos: (usize, usize), zoom: (f32, f32)) -> Result<()> {
+        let origin = cache.origin;
+        let offset = self.center(zoom, cols, rows * 2);
+
+        cache.update(self, RenderKey {
+            cols,
+            rows,
+            pos,
+            offset,
+            zoom,
+            sampling: self.sampling,
+            cell_aspect: self.cell_aspect,
+            linear: self.linear,
+            crop: self.crop,
+            orientation: self.orientation,
+            frame: self.frame,
+            options: *options,
+        });
+
+        let full = cache.begin_frame(cols, rows);
+        let padding = if options.charset.is_text() { Color::Reset } else { to_color(options, options.padding) };
 
-        for x in 0..twidth {
-            for y in 0..theight {
-                if x < offset.0 || y < offset.1 {
-                    queue!(term, cursor::MoveTo(x as u16, y as u16), style::PrintStyledContent(' '.on_black()))?;
+        let cells = {
+            let cache = &*cache;
+            collect(cols * rows, |i| {
+                let (x, y) = (i % cols, i / cols);
+                if x < offset.0 || (y * 2) + 1 < offset.1 {
+                    ' '.on(padding)
                 } else {
-                    let pix1 = self.pixel(((x - offset.0) + pos.0, ((y - offset.1) * 2) + pos.1), zoom);
-                    let pix2 = self.pixel(((x - offset.0) + pos.0, ((y - offset.1) * 2) + pos.1 + 1), zoom);
-                    queue!(term, cursor::MoveTo(x as u16, y as u16), style::PrintStyledContent(PIXEL_CHAR.with(Color::Rgb { r: pix1.r, g: pix1.g, b: pix1.b }).on(Color::Rgb { r: pix2.r, g: pix2.g, b: pix2.b })))?;
+                    let (ch, fg, bg) = cache.glyph(x, y);
+                    ch.with(fg).on(bg)
+                }
+            })
+        };
+
+        for (i, cell) in cells.into_iter().enumerate() {
+            let (x, y) = (i % cols, i / cols);
+            if cache.in_overlay(origin.0 + x, origin.1 + y) {
+                continue;
+            }
+            let changed = cache.swap_cell(x, y, cell);
+            if changed || full {
+                queue!(term, cursor::MoveTo((origin.0 + x) as u16, (origin.1 + y) as u16), style::PrintStyledContent(cell))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn ansi(&self, options: &RenderOptions, pos: (usize, usize), offset: (usize, usize), zoom: (f32, f32), width: usize, height: usize) -> String {
+        let mut out = String::new();
+        let rows = height.div_ceil(2);
+
+        let mut cache = RenderCache::default();
+        cache.update(self, RenderKey {
+            cols: width,
+            rows,
+            pos,
+            offset,
+            zoom,
+            sampling: self.sampling,
+            cell_aspect: self.cell_aspect,
+            linear: self.linear,
+            crop: self.crop,
+            orientation: self.orientation,
+            frame: self.frame,
+            options: *options,
+        });
+
+        for y in 0..rows {
+            let mut last = (Color::Reset, Color::Reset);
+            for x in 0..width {
+                let (ch, fg, bg) = cache.glyph(x, y);
+                let (ch, fg, bg) = if options.charset != Charset::HalfBlock || (y * 2) + 1 < height { (ch, fg, bg) } else { (PIXEL_CHAR, cache.color(x, y * 2), Color::Reset) };
+                if last != (fg, bg) {
+                    let _ = write!(out, "{}{}", SetForegroundColor(fg), SetBackgroundColor(bg));
+                    last = (fg, bg);
                 }
+                out.push(ch);
+            }
+            if last != (Color::Reset, Color::Reset) {
+                let _ = write!(out, "{}", ResetColor);
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn to_ansi_string(&self, cols: usize, rows: usize) -> String {
+        let (width, height) = self.view_size();
+        if cols == 0 || rows == 0 || width == 0 || height == 0 {
+            return String::new();
+        }
+
+        let z1 = cols as f32 / width as f32;
+        let z2 = (rows * 2) as f32 / height as f32;
+        let zoom = if z1 < z2 { z1 } else { z2 };
+        let (iwidth, iheight) = self.size((zoom, zoom));
+        self.ansi(&RenderOptions::default(), (0, 0), (0, 0), (zoom, zoom), iwidth.clamp(1, cols), iheight.clamp(1, rows * 2))
+    }
+
+    pub fn to_ansi_string_fit_width(&self, cols: usize, options: &RenderOptions) -> String {
+        let (width, height) = self.view_size();
+        if cols == 0 || width == 0 || height == 0 {
+            return String::new();
+        }
+
+        let zoom = if width > cols { cols as f32 / width as f32 } else { 1.0 };
+        let (iwidth, iheight) = self.size((zoom, zoom));
+        self.ansi(options, (0, 0), (0, 0), (zoom, zoom), iwidth.clamp(1, cols), iheight.max(1))
+    }
+
+    pub fn to_ansi_string_sized(&self, cols: Option<usize>, rows: Option<usize>, options: &RenderOptions) -> String {
+        let (width, height) = self.size((1.0, 1.0));
+        if width == 0 || height == 0 || cols == Some(0) || rows == Some(0) {
+            return String::new();
+        }
+
+        let zx = cols.map(|cols| cols as f32 / width as f32);
+        let zy = rows.map(|rows| (rows * 2) as f32 / height as f32);
+        let zoom = match (zx, zy) {
+            (Some(zx), Some(zy)) => zx.min(zy),
+            (zoom, None) | (None, zoom) => zoom.unwrap_or(1.0),
+        };
+        let iwidth = ((width as f32 * zoom).round() as usize).clamp(1, cols.unwrap_or(usize::MAX));
+        let iheight = ((height as f32 * zoom).round() as usize).clamp(1, rows.map_or(usize::MAX, |rows| rows * 2));
+        self.ansi(options, (0, 0), (0, 0), (zoom, zoom), iwidth, iheight)
+    }
+
+    pub fn to_ansi_string_grid(&self, cols: usize, rows: usize, options: &RenderOptions) -> String {
+        let (width, height) = self.size((1.0, 1.0));
+        if cols == 0 || rows == 0 || width == 0 || height == 0 {
+            return String::new();
+        }
+
+        let z = (cols as f32 / width as f32).min((rows * 2) as f32 / height as f32);
+        let offset = self.center((z, z), cols, rows * 2);
+        self.ansi(options, (0, 0), offset, (z, z), cols, rows * 2)
+    }
+
+    pub fn to_ansi_string_at(&self, cols: usize, options: &RenderOptions, pan: (usize, usize), zoom: (f32, f32)) -> String {
+        let (iwidth, iheight) = self.size(zoom);
+        if cols == 0 || iwidth == 0 || iheight == 0 {
+            return String::new();
+        }
+
+        let pos = self.view_pos(pan, zoom);
+        let pos = (pos.0.min(iwidth - 1), pos.1.min(iheight - 1));
+        self.ansi(options, pos, (0, 0), zoom, (iwidth - pos.0).min(cols), iheight - pos.1)
+    }
+
+    fn region_rgb(&self, options: &RenderOptions, pos: (usize, usize), zoom: (f32, f32), view: (usize, usize)) -> (usize, usize, Vec<u8>) {
+        let (width, height) = self.view_size();
+        let (zoom_x, zoom_y) = self.scale(zoom);
+        let x0 = ((pos.0 as f32 / zoom_x) as usize).min(width - 1);
+        let y0 = ((pos.1 as f32 / zoom_y) as usize).min(height - 1);
+        let x1 = (((pos.0 + view.0) as f32 / zoom_x).ceil() as usize).clamp(x0 + 1, width);
+        let y1 = (((pos.1 + view.1) as f32 / zoom_y).ceil() as usize).clamp(y0 + 1, height);
+
+        let (rwidth, rheight) = (x1 - x0, y1 - y0);
+        let pixels = collect(rwidth * rheight, |i| {
+            let (x, y) = (x0 + (i % rwidth), y0 + (i / rwidth));
+            let pix = options.apply(self.at(x, y));
+            if options.magenta && pix.a == 0 { MAGENTA } else { options.background.composite(pix, x, y) }
+        });
+        let pixels = match options.sharpen {
+            Some(amount) => sharpen(&pixels, rwidth, rheight, amount),
+            None => pixels,
+        };
+        (rwidth, rheight, pixels.iter().flat_map(|pix| [pix.r, pix.g, pix.b]).collect())
+    }
+
+    pub fn draw_kitty<W: Write>(&self, term: &mut W, cache: &mut RenderCache, options: &RenderOptions, (cols, rows): (usize, usize), pos: (usize, usize), zoom: (f32, f32)) -> Result<()> {
+        let (width, height) = self.view_size();
+        let offset = self.center(zoom, cols, rows * 2);
+
+        if !cache.begin_image(RenderKey { cols, rows, pos, offset, zoom, sampling: self.sampling, cell_aspect: self.cell_aspect, linear: self.linear, crop: self.crop, orientation: self.orientation, frame: self.frame, options: *options }) {
+            return Ok(());
+        }
+
+        let (iwidth, iheight) = self.size(zoom);
+        let vwidth = iwidth.saturating_sub(pos.0).min(cols.saturating_sub(offset.0));
+        let vheight = iheight.saturating_sub(pos.1).min((rows * 2).saturating_sub(offset.1));
+
+        write!(term, "\x1b_Ga=d,d=A,q=2\x1b\\")?;
+        if vwidth == 0 || vheight == 0 || width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let (rwidth, rheight, data) = self.region_rgb(options, pos, zoom, (vwidth, vheight));
+        let payload = base64::encode(&data);
+        let chunks: Vec<_> = payload.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+        queue!(term, cursor::MoveTo(offset.0 as u16, (offset.1 / 2) as u16))?;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = if i + 1 < chunks.len() { 1 } else { 0 };
+            if i == 0 {
+                write!(term, "\x1b_Ga=T,f=24,s={},v={},c={},r={},C=1,q=2,m={};", rwidth, rheight, vwidth, vheight.div_ceil(2), more)?;
+            } else {
+                write!(term, "\x1b_Gm={};", more)?;
             }
+            term.write_all(chunk)?;
+            write!(term, "\x1b\\")?;
         }
 
         Ok(())
     }
 
-    pub fn size(&self, zoom: f32) -> (usize, usize) {
-        ((self.width as f32 * zoom) as usize, (self.height as f32 * zoom) as usize)
+    pub fn draw_sixel<W: Write>(&self, term: &mut W, cache: &mut RenderCache, options: &RenderOptions, (cols, rows): (usize, usize), pos: (usize, usize), zoom: (f32, f32)) -> Result<()> {
+        let offset = self.center(zoom, cols, rows * 2);
+
+        if !cache.begin_image(RenderKey { cols, rows, pos, offset, zoom, sampling: self.sampling, cell_aspect: self.cell_aspect, linear: self.linear, crop: self.crop, orientation: self.orientation, frame: self.frame, options: *options }) {
+            return Ok(());
+        }
+
+        let (cwidth, cheight) = cell_pixels();
+        let (iwidth, iheight) = self.size(zoom);
+        let vwidth = iwidth.saturating_sub(pos.0).min(cols.saturating_sub(offset.0));
+        let vheight = iheight.saturating_sub(pos.1).min((rows * 2).saturating_sub(offset.1));
+
+        queue!(term, crossterm::terminal::Clear(crossterm::terminal::ClearType::All))?;
+        if vwidth == 0 || vheight == 0 {
+            return Ok(());
+        }
+
+        let width = vwidth * cwidth;
+        let height = vheight.div_ceil(2) * cheight;
+        let mut pixels = Vec::with_capacity(width * height);
+        for py in 0..height {
+            let y = (pos.1 as f32 + (py as f32 * 2.0 / cheight as f32)) / (zoom.1 * self.yscale());
+            for px in 0..width {
+                let x = (pos.0 as f32 + (px as f32 / cwidth as f32)) / zoom.0;
+                let pix = options.apply(self.sample(x, y));
+                pixels.push(if options.magenta && pix.a == 0 { MAGENTA } else { options.background.composite(pix, x as usize, y as usize) });
+            }
+        }
+
+        if let Some(amount) = options.sharpen {
+            pixels = sharpen(&pixels, width, height, amount);
+        }
+
+        queue!(term, cursor::MoveTo(offset.0 as u16, (offset.1 / 2) as u16))?;
+        term.write_all(sixel::encode(&pixels, width, height).as_bytes())?;
+
+        Ok(())
     }
 
-    pub fn pixel(&self, pos: (usize, usize), zoom: f32) -> Pixel {
-        let x = (pos.0 as f32 / zoom) as usize;
-        let y = (pos.1 as f32 / zoom) as usize;
+    pub fn draw_iterm2<W: Write>(&self, term: &mut W, cache: &mut RenderCache, options: &RenderOptions, (cols, rows): (usize, usize), pos: (usize, usize), zoom: (f32, f32)) -> Result<()> {
+        let (width, height) = self.view_size();
+        let offset = self.center(zoom, cols, rows * 2);
+
+        if !cache.begin_image(RenderKey { cols, rows, pos, offset, zoom, sampling: self.sampling, cell_aspect: self.cell_aspect, linear: self.linear, crop: self.crop, orientation: self.orientation, frame: self.frame, options: *options }) {
+            return Ok(());
+        }
+
+        let (iwidth, iheight) = self.size(zoom);
+        let vwidth = iwidth.saturating_sub(pos.0).min(cols.saturating_sub(offset.0));
+        let vheight = iheight.saturating_sub(pos.1).min((rows * 2).saturating_sub(offset.1));
+
+        queue!(term, crossterm::terminal::Clear(crossterm::terminal::ClearType::All))?;
+        if vwidth == 0 || vheight == 0 || width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let whole = vwidth == iwidth && vheight == iheight;
+        let untransformed = self.crop.is_none() && self.orientation == Orientation::default() && self.frames.len() == 1 && options.is_identity();
+        let encoded = if whole && untransformed && !self.data.is_empty() {
+            base64::encode(&self.data)
+        } else {
+            let (rwidth, rheight, data) = self.region_rgb(options, pos, zoom, (vwidth, vheight));
+            let region = image::RgbImage::from_raw(rwidth as u32, rheight as u32, data).expect("buffer matches region size");
+            let mut png = Vec::new();
+            region.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+            base64::encode(&png)
+        };
+
+        queue!(term, cursor::MoveTo(offset.0 as u16, (offset.1 / 2) as u16))?;
+        write!(term, "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=0:{}\x07", vwidth, vheight.div_ceil(2), encoded)?;
+
+        Ok(())
+    }
 
-        if x >= self.width || y >= self.height {
+    fn sample(&self, x: f32, y: f32) -> Pixel {
+        let (width, height) = self.view_size();
+        let x = x as usize;
+        let y = y as usize;
+        if x >= width || y >= height {
             Pixel::default()
         } else {
-            let pos = (y * self.width) + x;
-            self.pixels[pos].clone()
+            self.at(x, y)
         }
     }
-}
+
+    pub fn center(&self, zoom: (f32, f32), width: usize, height: usize) -> (usize, usize) {
+        let (iwidth, iheight) = self.size(zoom);
+        (width.saturating_sub(iwidth) / 2, height.saturating_sub(iheight) / 2)
+    }
+
+    pub fn palette(&self, colors: usize) -> Vec<(Pixel, f32)> {
+        let region = self.region();
+        let pixels: Vec<Pixel> = (region.y..region.y + region.height)
+            .flat_map(|y| &self.pixels()[(y * self.width) + region.x..(y * self.width) + region.x + region.width])
+            .copied()
+            .filter(|pix| pix.a > 0)
+            .collect();
+        let total = pixels.len().max(1) as f32;
+        quantize::median_cut(&pixels, colors).into_iter().map(|(pix, count)| (pix, count as f32 / total)).collect()
+    }
+
+    pub fn diff(&self, other: &Self) -> (Self, f32) {
+        let (a, b) = (self.view_size(), other.view_size());
+        let (width, height) = (a.0.max(b.0), a.1.max(b.1));
+        let mut same = 0;
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let left = (x < a.0 && y < a.1).then(|| self.at(x, y));
+                let right = (x < b.0 && y < b.1).then(|| other.at(x, y));
+                pixels.push(match (left, right) {
+                    (Some(left), Some(right)) if left == right => {
+                        same += 1;
+                        let v = left.luminance() / DIFF_DIM;
+                        Pixel { r: v, g: v, b: v, a: 255 }
+                    },
+                    _ => DIFF_CHANGED,
+                });
+            }
+        }
+        let mut im = Self::from_pixels(pixels, width, height);
+        im.cell_aspect = self.cell_aspect;
+        im.sampling = self.sampling;
+        im.linear = self.linear;
+        (im, same as f32 / (width * height).max(1) as f32)
+    }
+
+    pub fn histogram(&self) -> [[u32; 256]; 3] {
+        let mut hist = [[0; 256]; 3];
+        for pix in self.pixels() {
+            hist[0][pix.r as usize] += 1;
+            hist[1][pix.g as usize] += 1;
+            hist[2][pix.b as usize] += 1;
+        }
+        hist
+    }
+
+    pub fn pick(&self, pos: (usize, usize), zoom: (f32, f32)) -> Option<((usize, usize), Pixel)> {
+        let (width, height) = self.view_size();
+        let (zoom_x, zoom_y) = self.scale(zoom);
+        let x = (pos.0 as f32 / zoom_x) as usize;
+        let y = (pos.1 as f32 / zoom_y) as usize;
+        (x < width && y < height).then(|| ((x, y), self.at(x, y)))
+    }
+
+    pub fn locate(&self, point: (usize, usize), zoom: (f32, f32)) -> (f32, f32) {
+        let (zoom_x, zoom_y) = self.scale(zoom);
+        ((point.0 as f32 + 0.5) * zoom_x, (point.1 as f32 + 0.5) * zoom_y)
+    }
+
+    pub fn snapshot(&self, options: &RenderOptions, pos: (usize, usize), zoom: (f32, f32), view: (usize, usize)) -> image::RgbaImage {
+        let (iwidth, iheight) = self.size(zoom);
+        let width = iwidth.saturating_sub(pos.0).min(view.0);
+        let height = iheight.saturating_sub(pos.1).min(view.1);
+        image::RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+            let pix = options.apply(self.pixel((x as usize + pos.0, y as usize + pos.1), zoom));
+            image::Rgba([pix.r, pix.g, pix.b, pix.a])
+        })
+    }
+
+    pub fn view_pos(&self, pan: (usize, usize), zoom: (f32, f32)) -> (usize, usize) {
+        let (zoom_x, zoom_y) = self.scale(zoom);
+        ((pan.0 as f32 * zoom_x) as usize, (pan.1 as f32 * zoom_y) as usize)
+    }
+
+    pub fn size(&self, zoom: (f32, f32)) -> (usize, usize) {
+        let (width, height) = self.view_size();
+        let (zoom_x, zoom_y) = self.scale(zoom);
+        ((width as f32 * zoom_x).min(MAX_SIZE) as usize, (height as f32 * zoom_y).min(MAX_SIZE) as usize)
+    }
+
+    pub fn cell_aspect(&self) -> f32 {
+        self.cell_aspect
+    }
+
+    pub fn set_cell_aspect(&mut self, aspect: f32) {
+        self.cell_aspect = aspect;
+    }
+
+    fn yscale(&self) -> f32 {
+        DEFAULT_CELL_ASPECT / self.cell_aspect
+    }
+
+    pub fn linear(&self) -> bool {
+        self.linear
+    }
+
+    pub fn set_linear(&mut self, linear: bool) {
+        self.linear = linear;
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    pub fn set_sampling(&mut self, sampling: Sampling) {
+        self.sampling = sampling;
+    }
+
+    pub fn pixel(&self, pos: (usize, usize), zoom: (f32, f32)) -> Pixel {
+        self.subpixel(pos, zoom, (1, 2))
+    }
+
+    fn scale(&self, zoom: (f32, f32)) -> (f32, f32) {
+        (zoom.0, zoom.1 * self.yscale())
+    }
+
+    fn cell_zoom(&self, zoom: (f32, f32), cell: (usize, usize)) -> (f32, f32) {
+        let (zoom_x, zoom_y) = self.scale(zoom);
+        (zoom_x * cell.0 as f32, zoom_y * cell.1 as f32 / 2.0)
+    }
+
+    fn composite(&self, background: &Background, pix: Pixel, pos: (usize, usize), zoom: (f32, f32), cell: (usize, usize)) -> Pixel {
+        let zoom = self.cell_zoom(zoom, cell);
+        background.composite(pix, (pos.0 as f32 / zoom.0) as usize, (pos.1 as f32 / zoom.1) as usize)
+    }
+
+    fn contains(&self, pos: (usize, usize), zoom: (f32, f32), cell: (usize, usize)) -> bool {
+        let (width, height) = self.view_size();
+        let zoom = self.cell_zoom(zoom, cell);
+        ((pos.0 as f32 / zoom.0) as usize) < width && ((pos.1 as f32 / zoom.1) as usize) < height
+    }
+
+    fn on_grid(&self, pos: (usize, usize), zoom: (f32, f32), cell: (usize, usize)) -> bool {
+        let zoom = self.cell_zoom(zoom, cell);
+        let edge = |p: usize, z: f32| p > 0 && (p as f32 / z) as usize != ((p - 1) as f32 / z) as usize;
+        edge(pos.0, zoom.0) || edge(pos.1, zoom.1)
+    }
+
+    fn subpixel(&self, pos: (usize, usize), zoom: (f32, f32), cell: (usize, usize)) -> Pixel {
+        let zoom = self.cell_zoom(zoom, cell);
+        if self.linear && (zoom.0 < 1.0 || zoom.1 < 1.0) {
+            return self.pixel_area(pos, zoom);
+        }
+        match self.sampling {
+            Sampling::Nearest => self.pixel_nearest(pos, zoom),
+            Sampling::Bilinear => self.pixel_bilinear(pos, zoom),
+            Sampling::Area => self.pixel_area(pos, zoom),
+        }
+    }
+
+    fn pixel_nearest(&self, pos: (usize, usize), zoom: (f32, f32)) -> Pixel {
+        let (width, height) = self.view_size();
+        let x = (pos.0 as f32 / zoom.0) as usize;
+        let y = (pos.1 as f32 / zoom.1) as usize;
+
+        if x >= width || y >= height {
+            Pixel::default()
+        } else {
+            self.at(x, y)
+        }
+    }
+
+    fn pixel_bilinear(&self, pos: (usize, usize), zoom: (f32, f32)) -> Pixel {
+        let (width, height) = self.view_size();
+        let x = (pos.0 as f32 / zoom.0) as usize;
+        let y = (pos.1 as f32 / zoom.1) as usize;
+
+        if x >= width || y >= height {
+            return Pixel::default();
+        }
+
+        let fx = ((pos.0 as f32 + 0.5) / zoom.0 - 0.5).max(0.0);
+        let fy = ((pos.1 as f32 + 0.5) / zoom.1 - 0.5).max(0.0);
+        let x0 = (fx as usize).min(width - 1);
+        let y0 = (fy as usize).min(height - 1);
+        let x1 = (x0 + 1).min(width - 1);
+        let y1 = (y0 + 1).min(height - 1);
+        let tx = (fx - x0 as f32).clamp(0.0, 1.0);
+        let ty = (fy - y0 as f32).clamp(0.0, 1.0);
+
+        let p00 = self.at(x0, y0);
+        let p10 = self.at(x1, y0);
+        let p01 = self.at(x0, y1);
+        let p11 = self.at(x1, y1);
+
+        let blend = |c00: u8, c10: u8, c01: u8, c11: u8| -> u8 {
+            if self.linear {
+                let top = lerp(to_linear(c00), to_linear(c10), tx);
+                let bottom = lerp(to_linear(c01), to_linear(c11), tx);
+                from_linear(lerp(top, bottom, ty))
+            } else {
+                let top = lerp(c00 as f32, c10 as f32, tx);
+                let bottom = lerp(c01 as f32, c11 as f32, tx);
+                lerp(top, bottom, ty).round() as u8
+            }
+        };
+
+        Pixel {
+            r: blend(p00.r, p10.r, p01.r, p11.r),
+            g: blend(p00.g, p10.g, p01.g, p11.g),
+            b: blend(p00.b, p10.b, p01.b, p11.b),
+            a: lerp(lerp(p00.a as f32, p10.a as f32, tx), lerp(p01.a as f32, p11.a as f32, tx), ty).round() as u8,
+        }
+    }
+
+    fn pixel_area(&self, pos: (usize, usize), zoom: (f32, f32)) -> Pixel {
+        let (width, height) = self.view_size();
+        let x0 = (pos.0 as f32 / zoom.0) as usize;
+        let y0 = (pos.1 as f32 / zoom.1) as usize;
+        if x0 >= width || y0 >= height {
+            return Pixel::default();
+        }
+        let x1 = (((pos.0 + 1) as f32 / zoom.0).ceil() as usize).clamp(x0 + 1, width);
+        let y1 = (((pos.1 + 1) as f32 / zoom.1).ceil() as usize).clamp(y0 + 1, height);
+        let xstep = (x1 - x0).div_ceil(MAX_AREA_SAMPLES);
+        let ystep = (y1 - y0).div_ceil(MAX_AREA_SAMPLES);
+
+        let (mut r, mut g, mut b, mut a, mut n) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for y in (y0..y1).step_by(ystep) {
+            for x in (x0..x1).step_by(xstep) {
+                let pix = self.at(x, y);
+                if self.linear {
+                    r += to_linear(pix.r);
+                    g += to_linear(pix.g);
+                    b += to_linear(pix.b);
+                } else {
+                    r += pix.r as f32;
+                    g += pix.g as f32;
+                    b += pix.b as f32;
+                }
+                a += pix.a as f32;
+                n += 1.0;
+            }
+        }
+        if self.linear {
+            Pixel { r: from_linear(r / n), g: from_linear(g / n), b: from_linear(b / n), a: (a / n).round() as u8 }
+        } else {
+            let mean = |v: f32| (v / n).round() as u8;
+            Pixel { r: mean(r), g: mean(g), b: mean(b), a: mean(a) }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use tim::Image;
+
+pub const FRAME: Duration = Duration::from_millis(50);
+const MAX_SCALE: f32 = 1.3;
+const FOCUS_RANGE: (f32, f32) = (0.3, 0.7);
+
+type Framing = (f32, (f32, f32));
+
+fn random() -> f32 {
+    (RandomState::new().build_hasher().finish() >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn framing() -> Framing {
+    (lerp(1.0, MAX_SCALE, random()), (lerp(FOCUS_RANGE.0, FOCUS_RANGE.1, random()), lerp(FOCUS_RANGE.0, FOCUS_RANGE.1, random())))
+}
+
+pub struct KenBurns {
+    zoom: (f32, f32),
+    from: Framing,
+    to: Framing,
+}
+
+impl KenBurns {
+    pub fn new(zoom: (f32, f32)) -> Self {
+        Self { zoom, from: framing(), to: framing() }
+    }
+
+    pub fn frame(&self, im: &Image, t: f32, twidth: usize, theight: usize) -> ((f32, f32), (usize, usize)) {
+        let t = t.clamp(0.0, 1.0);
+        let t = t * t * (3.0 - (2.0 * t));
+        let scale = lerp(self.from.0, self.to.0, t);
+        let zoom = crate::clamp_zoom((self.zoom.0 * scale, self.zoom.1 * scale), twidth, theight);
+        let focus = (lerp(self.from.1 .0, self.to.1 .0, t), lerp(self.from.1 .1, self.to.1 .1, t));
+        (zoom, crate::center_on(im, zoom, focus, twidth, theight))
+    }
+}
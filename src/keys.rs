@@ -0,0 +1,571 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Redraw,
+    ZoomIn,
+    ZoomOut,
+    ZoomInLarge,
+    ZoomOutLarge,
+    ActualSize,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    LeftEdge,
+    RightEdge,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    ToggleSampling,
+    PlayPause,
+    NextFrame,
+    PrevFrame,
+    SpeedUp,
+    SpeedDown,
+    RotateLeft,
+    RotateRight,
+    FlipHorizontal,
+    FlipVertical,
+    BrightnessUp,
+    BrightnessDown,
+    ContrastUp,
+    ContrastDown,
+    Grayscale,
+    Threshold,
+    ThresholdUp,
+    ThresholdDown,
+    WindowWider,
+    WindowNarrower,
+    LevelUp,
+    LevelDown,
+    Histogram,
+    Grid,
+    Sharpen,
+    Picker,
+    CopyColor,
+    RulerStart,
+    RulerEnd,
+    Invert,
+    Magenta,
+    Simulate,
+    SaveView,
+    Info,
+    PauseSlideshow,
+    NextImage,
+    PrevImage,
+    Reset,
+    ToggleSync,
+    SwitchSide,
+    Open,
+    Help,
+}
+
+const ACTIONS: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+    ("redraw", Action::Redraw),
+    ("zoom-in", Action::ZoomIn),
+    ("zoom-out", Action::ZoomOut),
+    ("zoom-in-large", Action::ZoomInLarge),
+    ("zoom-out-large", Action::ZoomOutLarge),
+    ("actual-size", Action::ActualSize),
+    ("pan-left", Action::PanLeft),
+    ("pan-right", Action::PanRight),
+    ("pan-up", Action::PanUp),
+    ("pan-down", Action::PanDown),
+    ("left-edge", Action::LeftEdge),
+    ("right-edge", Action::RightEdge),
+    ("page-up", Action::PageUp),
+    ("page-down", Action::PageDown),
+    ("top", Action::Top),
+    ("bottom", Action::Bottom),
+    ("toggle-sampling", Action::ToggleSampling),
+    ("play-pause", Action::PlayPause),
+    ("next-frame", Action::NextFrame),
+    ("prev-frame", Action::PrevFrame),
+    ("speed-up", Action::SpeedUp),
+    ("speed-down", Action::SpeedDown),
+    ("rotate-left", Action::RotateLeft),
+    ("rotate-right", Action::RotateRight),
+    ("flip-horizontal", Action::FlipHorizontal),
+    ("flip-vertical", Action::FlipVertical),
+    ("brightness-up", Action::BrightnessUp),
+    ("brightness-down", Action::BrightnessDown),
+    ("contrast-up", Action::ContrastUp),
+    ("contrast-down", Action::ContrastDown),
+    ("grayscale", Action::Grayscale),
+    ("threshold", Action::Threshold),
+    ("threshold-up", Action::ThresholdUp),
+    ("threshold-down", Action::ThresholdDown),
+    ("window-wider", Action::WindowWider),
+    ("window-narrower", Action::WindowNarrower),
+    ("level-up", Action::LevelUp),
+    ("level-down", Action::LevelDown),
+    ("histogram", Action::Histogram),
+    ("grid", Action::Grid),
+    ("sharpen", Action::Sharpen),
+    ("picker", Action::Picker),
+    ("copy-color", Action::CopyColor),
+    ("ruler-start", Action::RulerStart),
+    ("ruler-end", Action::RulerEnd),
+    ("invert", Action::Invert),
+    ("magenta", Action::Magenta),
+    ("simulate", Action::Simulate),
+    ("save-view", Action::SaveView),
+    ("info", Action::Info),
+    ("pause-slideshow", Action::PauseSlideshow),
+    ("next-image", Action::NextImage),
+    ("prev-image", Action::PrevImage),
+    ("reset", Action::Reset),
+    ("toggle-sync", Action::ToggleSync),
+    ("switch-side", Action::SwitchSide),
+    ("open", Action::Open),
+    ("help", Action::Help),
+];
+
+const DEFAULTS: &[(Action, &[&str])] = &[
+    (Action::Quit, &["q", "Esc", "Ctrl-c"]),
+    (Action::Redraw, &["Ctrl-l"]),
+    (Action::ZoomIn, &["="]),
+    (Action::ZoomOut, &["-"]),
+    (Action::ZoomInLarge, &["+"]),
+    (Action::ZoomOutLarge, &["_"]),
+    (Action::ActualSize, &["0"]),
+    (Action::PanLeft, &["h", "a", "Left"]),
+    (Action::PanRight, &["l", "d", "Right"]),
+    (Action::PanUp, &["k", "w", "Up"]),
+    (Action::PanDown, &["j", "s", "Down"]),
+    (Action::LeftEdge, &["Home"]),
+    (Action::RightEdge, &["End"]),
+    (Action::PageUp, &["PageUp"]),
+    (Action::PageDown, &["PageDown"]),
+    (Action::Top, &["gg"]),
+    (Action::Bottom, &["G"]),
+    (Action::ToggleSampling, &["b"]),
+    (Action::PlayPause, &["p"]),
+    (Action::NextFrame, &["."]),
+    (Action::PrevFrame, &[","]),
+    (Action::SpeedUp, &["*"]),
+    (Action::SpeedDown, &["/"]),
+    (Action::RotateLeft, &["["]),
+    (Action::RotateRight, &["]"]),
+    (Action::FlipHorizontal, &["f"]),
+    (Action::FlipVertical, &["F"]),
+    (Action::BrightnessUp, &[")"]),
+    (Action::BrightnessDown, &["("]),
+    (Action::ContrastUp, &[">"]),
+    (Action::ContrastDown, &["<"]),
+    (Action::Grayscale, &["g"]),
+    (Action::Threshold, &["t"]),
+    (Action::ThresholdUp, &["}"]),
+    (Action::ThresholdDown, &["{"]),
+    (Action::WindowWider, &["y"]),
+    (Action::WindowNarrower, &["Y"]),
+    (Action::LevelUp, &["u"]),
+    (Action::LevelDown, &["U"]),
+    (Action::Histogram, &["H"]),
+    (Action::Grid, &["#"]),
+    (Action::Sharpen, &["o"]),
+    (Action::Picker, &["x"]),
+    (Action::CopyColor, &["Enter"]),
+    (Action::RulerStart, &["r"]),
+    (Action::RulerEnd, &["R"]),
+    (Action::Invert, &["v"]),
+    (Action::Magenta, &["m"]),
+    (Action::Simulate, &["c"]),
+    (Action::SaveView, &["S"]),
+    (Action::Info, &["i"]),
+    (Action::PauseSlideshow, &["P"]),
+    (Action::NextImage, &["n"]),
+    (Action::PrevImage, &["N"]),
+    (Action::Reset, &["Space"]),
+    (Action::ToggleSync, &["z"]),
+    (Action::SwitchSide, &["Tab"]),
+    (Action::Open, &["Enter"]),
+    (Action::Help, &["?"]),
+];
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("Esc", KeyCode::Esc),
+    ("Enter", KeyCode::Enter),
+    ("Space", KeyCode::Char(' ')),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    ctrl: bool,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self { code: event.code, ctrl: event.modifiers.contains(KeyModifiers::CONTROL) }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl-")?;
+        }
+        match NAMED_KEYS.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match self.code {
+                KeyCode::Char(c) => write!(f, "{}", c),
+                code => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+fn parse_key(s: &str) -> Result<Vec<Key>> {
+    let (ctrl, name) = match s.strip_prefix("Ctrl-") {
+        Some(name) => (true, name),
+        None => (false, s),
+    };
+    if let Some((_, code)) = NAMED_KEYS.iter().find(|(n, _)| *n == name) {
+        return Ok(vec![Key { code: *code, ctrl }]);
+    }
+    let chars: Vec<char> = name.chars().collect();
+    match chars.as_slice() {
+        [c] => Ok(vec![Key { code: KeyCode::Char(*c), ctrl }]),
+        [a, b] if !ctrl => Ok(vec![Key { code: KeyCode::Char(*a), ctrl }, Key { code: KeyCode::Char(*b), ctrl }]),
+        _ => bail!("invalid key: {}", s),
+    }
+}
+
+pub struct Keymap {
+    bindings: Vec<(Vec<Key>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings = Vec::new();
+        for (action, keys) in DEFAULTS {
+            for key in *keys {
+                bindings.push((parse_key(key).expect("default keys are valid"), *action));
+            }
+        }
+        Self { bindings }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+    .map(|dir| dir.join("tim").join("config.toml"))
+}
+
+fn parse_string(s: &str) -> Result<String> {
+    let invalid = || anyhow!("expected a quoted string: {}", s);
+    if let Some(inner) = s.strip_prefix('\'') {
+        return inner.strip_suffix('\'').filter(|inner| !inner.contains('\'')).map(str::to_string).ok_or_else(invalid);
+    }
+    let mut chars = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')).ok_or_else(invalid)?.chars();
+    let mut string = String::new();
+    while let Some(c) = chars.next() {
+        string.push(match c {
+            '"' => return Err(invalid()),
+            '\\' => match chars.next() {
+                Some('\\') => '\\',
+                Some('"') => '"',
+                Some('t') => '\t',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some(u @ ('u' | 'U')) => {
+                    let len = if u == 'u' { 4 } else { 8 };
+                    let hex: String = chars.by_ref().take(len).collect();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == len && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| anyhow!("invalid escape: \\{}{}", u, hex))?
+                },
+                Some(c) => bail!("invalid escape: \\{}", c),
+                None => return Err(invalid()),
+            },
+            c => c,
+        });
+    }
+    Ok(string)
+}
+
+fn string_len(s: &str) -> Option<usize> {
+    let quote = s.chars().next()?;
+    let mut escaped = false;
+    s.char_indices().skip(1).find(|&(_, c)| {
+        let end = c == quote && !escaped;
+        escaped = quote == '"' && c == '\\' && !escaped;
+        end
+    })
+    .map(|(i, _)| i + 1)
+}
+
+fn unquoted(s: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+    s.char_indices().filter(move |&(_, c)| {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => return true,
+        }
+        false
+    })
+}
+
+fn split_list(list: &str) -> Result<Vec<&str>> {
+    let mut items = Vec::new();
+    let mut rest = list.trim();
+    while !rest.is_empty() {
+        let end = match rest.chars().next() {
+            Some('"' | '\'') => string_len(rest).ok_or_else(|| anyhow!("unterminated string: {}", rest))?,
+            _ => rest.find(',').unwrap_or(rest.len()),
+        };
+        items.push(rest[..end].trim());
+        rest = rest[end..].trim_start();
+        if let Some(next) = rest.strip_prefix(',') {
+            rest = next.trim_start();
+        } else if !rest.is_empty() {
+            bail!("expected `,` between list items: {}", list);
+        }
+    }
+    Ok(items)
+}
+
+fn parse_value(s: &str) -> Result<Vec<String>> {
+    match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        Some(list) => split_list(list)?.into_iter().map(parse_string).collect(),
+        None => Ok(vec![parse_string(s)?]),
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    unquoted(line).find(|&(_, c)| c == '#').map_or(line, |(i, _)| &line[..i])
+}
+
+fn open_list(line: &str) -> bool {
+    line.split_once('=').is_some_and(|(_, value)| value.trim_start().starts_with('[') && !unquoted(value).any(|(_, c)| c == ']'))
+}
+
+impl Keymap {
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(&path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| anyhow!("{}:{}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut keymap = Self::default();
+        let mut in_keys = false;
+        let mut entry: Option<(usize, String)> = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            let (i, line) = match entry.take() {
+                Some((start, entry)) => (start, entry + " " + line),
+                None if line.is_empty() => continue,
+                None => (i, line.to_string()),
+            };
+            if let Some(table) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_keys = table.trim() == "keys";
+                continue;
+            }
+            if !in_keys {
+                continue;
+            }
+            if open_list(&line) {
+                entry = Some((i, line));
+                continue;
+            }
+            let result = line.split_once('=').ok_or_else(|| anyhow!("expected `action = \"key\"`")).and_then(|(name, value)| {
+                let name = name.trim();
+                let action = ACTIONS.iter().find(|(n, _)| *n == name).map(|(_, a)| *a).ok_or_else(|| anyhow!("unknown action: {}", name))?;
+                let keys = parse_value(value.trim())?.iter().map(|key| parse_key(key)).collect::<Result<Vec<_>>>()?;
+                keymap.bindings.retain(|(k, a)| *a != action && !keys.contains(k));
+                keymap.bindings.extend(keys.into_iter().map(|key| (key, action)));
+                Ok(())
+            });
+            result.map_err(|e| anyhow!("{}: {}", i + 1, e))?;
+        }
+        if let Some((i, _)) = entry {
+            bail!("{}: unterminated list", i + 1);
+        }
+        Ok(keymap)
+    }
+
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        ACTIONS
+            .iter()
+            .filter_map(|(name, action)| {
+                let keys: Vec<String> = self.bindings
+                    .iter()
+                    .filter(|(_, a)| a == action)
+                    .map(|(keys, _)| keys.iter().map(Key::to_string).collect())
+                    .collect();
+                (!keys.is_empty()).then(|| (keys.join(" "), *name))
+            })
+            .collect()
+    }
+
+    pub fn is_bound(&self, key: Key) -> bool {
+        self.bindings.iter().any(|(k, _)| k[0] == key)
+    }
+
+    fn find(&self, keys: &[Key], applies: &impl Fn(Action) -> bool) -> Option<Action> {
+        self.bindings.iter().find(|(k, a)| k == keys && applies(*a)).map(|(_, a)| *a)
+    }
+
+    pub fn lookup(&self, pending: &mut Option<Key>, key: Key, applies: impl Fn(Action) -> bool) -> (Option<Action>, bool) {
+        if let Some(prev) = pending.take() {
+            if let Some(action) = self.find(&[prev, key], &applies) {
+                return (Some(action), false);
+            }
+            if let Some(action) = self.find(&[prev], &applies) {
+                return (Some(action), true);
+            }
+        }
+        if self.bindings.iter().any(|(k, a)| k.len() == 2 && k[0] == key && applies(*a)) {
+            *pending = Some(key);
+            return (None, false);
+        }
+        (self.find(&[key], &applies), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> Key {
+        Key { code: KeyCode::Char(c), ctrl: false }
+    }
+
+    fn action(keymap: &Keymap, keys: &[Key]) -> Option<Action> {
+        keymap.find(keys, &|_| true)
+    }
+
+    #[test]
+    fn empty_config_keeps_the_defaults() {
+        let keymap = Keymap::parse("").unwrap();
+        assert_eq!(action(&keymap, &[key('q')]), Some(Action::Quit));
+        assert_eq!(action(&keymap, &[key('g'), key('g')]), Some(Action::Top));
+    }
+
+    #[test]
+    fn bindings_replace_the_defaults_of_their_action() {
+        let keymap = Keymap::parse("[keys]\nquit = \"Q\"\npan-left = [\"a\", \"Left\"]\n").unwrap();
+        assert_eq!(action(&keymap, &[key('Q')]), Some(Action::Quit));
+        assert_eq!(action(&keymap, &[key('q')]), None);
+        assert_eq!(action(&keymap, &[key('a')]), Some(Action::PanLeft));
+        assert_eq!(action(&keymap, &[Key { code: KeyCode::Left, ctrl: false }]), Some(Action::PanLeft));
+        assert_eq!(action(&keymap, &[key('h')]), None);
+    }
+
+    #[test]
+    fn lists_can_contain_commas() {
+        let keymap = Keymap::parse("[keys]\nnext-frame = [\",\" , \".\"]\n").unwrap();
+        assert_eq!(action(&keymap, &[key(',')]), Some(Action::NextFrame));
+        assert_eq!(action(&keymap, &[key('.')]), Some(Action::NextFrame));
+        assert!(keymap.bindings.iter().all(|(_, a)| *a != Action::PrevFrame));
+    }
+
+    #[test]
+    fn comments_are_ignored_outside_strings() {
+        let keymap = Keymap::parse("# tim\n[keys] # bindings\nzoom-in = \"+\"  # bigger\ngrid = [\"#\"] # hash\n").unwrap();
+        assert_eq!(action(&keymap, &[key('+')]), Some(Action::ZoomIn));
+        assert_eq!(action(&keymap, &[key('#')]), Some(Action::Grid));
+    }
+
+    #[test]
+    fn other_tables_are_ignored() {
+        let keymap = Keymap::parse("[view]\nquit = \"Q\"\n").unwrap();
+        assert_eq!(action(&keymap, &[key('q')]), Some(Action::Quit));
+        assert_eq!(action(&keymap, &[key('Q')]), None);
+    }
+
+    #[test]
+    fn prefix_keys_wait_for_the_next_key() {
+        let keymap = Keymap::default();
+        let mut pending = None;
+        assert_eq!(keymap.lookup(&mut pending, key('g'), |_| true), (None, false));
+        assert_eq!(keymap.lookup(&mut pending, key('g'), |_| true), (Some(Action::Top), false));
+        assert_eq!(pending, None);
+
+        assert_eq!(keymap.lookup(&mut pending, key('g'), |_| true), (None, false));
+        assert_eq!(keymap.lookup(&mut pending, key('j'), |_| true), (Some(Action::Grayscale), true));
+        assert_eq!(keymap.lookup(&mut pending, key('j'), |_| true), (Some(Action::PanDown), false));
+    }
+
+    #[test]
+    fn prefix_keys_fire_at_once_when_the_sequence_does_not_apply() {
+        let keymap = Keymap::default();
+        let mut pending = None;
+        assert_eq!(keymap.lookup(&mut pending, key('g'), |action| action != Action::Top), (Some(Action::Grayscale), false));
+        assert_eq!(pending, None);
+    }
+
+    #[test]
+    fn strings_can_be_escaped_or_literal() {
+        let keymap = Keymap::parse("[keys]\ngrid = [\"\\\\\", \"\\\"\", '#']\ninfo = \"\\u0049\"\n").unwrap();
+        assert_eq!(action(&keymap, &[key('\\')]), Some(Action::Grid));
+        assert_eq!(action(&keymap, &[key('"')]), Some(Action::Grid));
+        assert_eq!(action(&keymap, &[key('#')]), Some(Action::Grid));
+        assert_eq!(action(&keymap, &[key('I')]), Some(Action::Info));
+        let keymap = Keymap::parse("[keys]\nreset = '\\'\n").unwrap();
+        assert_eq!(action(&keymap, &[key('\\')]), Some(Action::Reset));
+    }
+
+    #[test]
+    fn lists_can_span_lines() {
+        let keymap = Keymap::parse("[keys]\nquit = [\n    \"Q\",  # shout\n    ']',\n]\nhelp = \"H\"\n").unwrap();
+        assert_eq!(action(&keymap, &[key('Q')]), Some(Action::Quit));
+        assert_eq!(action(&keymap, &[key(']')]), Some(Action::Quit));
+        assert_eq!(action(&keymap, &[key('H')]), Some(Action::Help));
+    }
+
+    #[test]
+    fn p_only_plays_and_pauses() {
+        let keymap = Keymap::default();
+        let mut pending = None;
+        assert_eq!(keymap.lookup(&mut pending, key('p'), |_| true), (Some(Action::PlayPause), false));
+        assert_eq!(keymap.lookup(&mut pending, key('p'), |action| action != Action::PlayPause), (None, false));
+        assert_eq!(keymap.lookup(&mut pending, key('N'), |_| true), (Some(Action::PrevImage), false));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = |text| Keymap::parse(text).err().unwrap().to_string();
+        assert_eq!(err("[keys]\nfly = \"f\"\n"), "2: unknown action: fly");
+        assert_eq!(err("[keys]\n\nquit = q\n"), "3: expected a quoted string: q");
+        assert_eq!(err("[keys]\nquit = [\"q\" \"x\"]\n"), "2: expected `,` between list items: \"q\" \"x\"");
+        assert_eq!(err("[keys]\nquit = [\"q]\n"), "2: unterminated list");
+        assert_eq!(err("[keys]\nquit\n"), "2: expected `action = \"key\"`");
+        assert_eq!(err("[keys]\nquit = \"\\q\"\n"), "2: invalid escape: \\q");
+        assert_eq!(err("[keys]\nquit = [\n\"q\",\n"), "2: unterminated list");
+    }
+}
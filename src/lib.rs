@@ -0,0 +1,12 @@
+mod base64;
+pub mod color;
+#[cfg(feature = "dicom")]
+mod dicom;
+mod exif;
+#[cfg(feature = "icc")]
+mod icc;
+pub mod image;
+pub mod quantize;
+mod sixel;
+
+pub use crate::image::{Image, Pixel};
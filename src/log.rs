@@ -0,0 +1,25 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+
+static LOG: OnceLock<Mutex<File>> = OnceLock::new();
+
+pub fn open(path: &str) -> Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| anyhow!("{}: {}", path, e))?;
+    let _ = LOG.set(Mutex::new(file));
+    Ok(())
+}
+
+pub fn write(args: fmt::Arguments) {
+    let Some(file) = LOG.get() else {
+        return;
+    };
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    if let Ok(mut file) = file.lock() {
+        let _ = writeln!(file, "{}.{:03} {}", time.as_secs(), time.subsec_millis(), args);
+    }
+}
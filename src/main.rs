@@ -1,108 +1,791 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEventKind},
-    terminal::{self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand,
-    QueueableCommand,
+    event::{self, Event, KeyEventKind, MouseButton, MouseEventKind},
     style::{self, Stylize},
-    queue,
+    terminal,
     execute,
+    queue,
 };
 
-mod image;
+use tim::{color, image};
+
+use crate::keys::Action;
+
+mod args;
+mod cache;
+mod compare;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod gallery;
+mod keys;
+mod ken_burns;
+mod log;
+#[cfg(feature = "net")]
+mod net;
+mod sheet;
+mod watch;
+
+const ZOOM_STEP: f32 = 1.1;
+const ZOOM_STEP_LARGE: f32 = 1.5;
+const KEY_TIMEOUT: Duration = Duration::from_secs(1);
+const MIN_ZOOM: f32 = 0.01;
+const CLICK_DISTANCE: u16 = 1;
+const MAX_COUNT: usize = 999;
+const HISTOGRAM_SIZE: (usize, usize) = (32, 8);
+const BRIGHTNESS_STEP: i16 = 8;
+const CONTRAST_STEP: f32 = 0.1;
+const CONTRAST_RANGE: (f32, f32) = (0.1, 4.0);
+const THRESHOLD_STEP: u8 = 8;
+const WINDOW_STEP: f32 = 1.1;
+const LEVEL_STEP: f32 = 0.05;
+const SPEED_STEP: f32 = 1.25;
+const SPEED_RANGE: (f32, f32) = (0.1, 10.0);
 
-fn ui_loop<W: Write>(term: &mut W, im: &image::Image) -> Result<()> {
-    execute!(term, terminal::Clear(terminal::ClearType::All))?;
-    let mut zoom = 1.0;
-    let ws = terminal::window_size()?;
-    let twidth = ws.columns as usize;
-    let theight = ws.rows as usize * 2;
-    let (mut iwidth, mut iheight) = im.size(zoom);
-    if iwidth > twidth || iheight > theight {
-        let z1 = (twidth as f32) / (iwidth as f32);
-        let z2 = (theight as f32) / (iheight as f32);
-        zoom = if z1 < z2 { z1 } else { z2 };
-        let (w, h) = im.size(zoom);
-        iwidth = w;
-        iheight = h;
+fn fit_view(im: &image::Image, twidth: usize, theight: usize, fit: args::Fit) -> ((f32, f32), (usize, usize)) {
+    let (iwidth, iheight) = im.size((1.0, 1.0));
+    let (iwidth, iheight) = (iwidth.max(1), iheight.max(1));
+    let z1 = ((twidth as f32) / (iwidth as f32)).max(MIN_ZOOM);
+    let z2 = ((theight as f32) / (iheight as f32)).max(MIN_ZOOM);
+    match fit {
+        args::Fit::Contain => {
+            let zoom = if iwidth > twidth || iheight > theight {
+                if z1 < z2 { z1 } else { z2 }
+            } else {
+                1.0
+            };
+            ((zoom, zoom), (0, 0))
+        },
+        args::Fit::Width => ((z1, z1), (0, 0)),
+        args::Fit::Height => ((z2, z2), (0, 0)),
+        args::Fit::Fill => {
+            let zoom = if z1 > z2 { z1 } else { z2 };
+            ((zoom, zoom), center_on(im, (zoom, zoom), (0.5, 0.5), twidth, theight))
+        },
+        args::Fit::Stretch => ((z1, z2), (0, 0)),
+        args::Fit::Center => ((1.0, 1.0), center_on(im, (1.0, 1.0), (0.5, 0.5), twidth, theight)),
+        args::Fit::None => ((1.0, 1.0), (0, 0)),
     }
+}
 
-    let mut pos = (0, 0);
-    let mut offset = (0, 0);
+fn clamp_zoom(zoom: (f32, f32), twidth: usize, theight: usize) -> (f32, f32) {
+    let limit = (twidth.max(1) as f32 / zoom.0).min(theight.max(1) as f32 / zoom.1).min(1.0);
+    (zoom.0 * limit, zoom.1 * limit)
+}
+
+fn zoom_at(zoom: (f32, f32), new_zoom: (f32, f32), pos: (usize, usize), anchor: (f32, f32), new_anchor: (f32, f32)) -> (usize, usize) {
+    let x = (((pos.0 as f32 + anchor.0) / zoom.0) * new_zoom.0) - new_anchor.0;
+    let y = (((pos.1 as f32 + anchor.1) / zoom.1) * new_zoom.1) - new_anchor.1;
+    (x.max(0.0) as usize, y.max(0.0) as usize)
+}
+
+fn view_center(im: &image::Image, zoom: (f32, f32), pos: (usize, usize), twidth: usize, theight: usize) -> (f32, f32) {
+    let (iwidth, iheight) = im.size(zoom);
+    (
+        (pos.0 as f32 + (iwidth.min(twidth) as f32 / 2.0)) / iwidth.max(1) as f32,
+        (pos.1 as f32 + (iheight.min(theight) as f32 / 2.0)) / iheight.max(1) as f32,
+    )
+}
+
+fn center_on(im: &image::Image, zoom: (f32, f32), focus: (f32, f32), twidth: usize, theight: usize) -> (usize, usize) {
+    let (iwidth, iheight) = im.size(zoom);
+    let x = (focus.0 * iwidth as f32) - (iwidth.min(twidth) as f32 / 2.0);
+    let y = (focus.1 * iheight as f32) - (iheight.min(theight) as f32 / 2.0);
+    (x.max(0.0) as usize, y.max(0.0) as usize)
+}
+
+fn draw_status<W: Write>(term: &mut W, cols: usize, row: usize, text: &str) -> Result<()> {
+    let text: String = text.chars().take(cols).collect();
+    let pad = cols.saturating_sub(text.chars().count());
+    queue!(term, cursor::MoveTo(0, row as u16), style::PrintStyledContent(format!("{}{}", text, " ".repeat(pad)).reverse()))?;
+    Ok(())
+}
+
+fn draw_histogram<W: Write>(term: &mut W, hist: &[[u32; 256]; 3], x: usize, y: usize) -> Result<()> {
+    let (width, height) = HISTOGRAM_SIZE;
+    let bin = 256 / width;
+    let bins: Vec<[u32; 3]> = (0..width)
+        .map(|i| [0, 1, 2].map(|c| hist[c][i * bin..(i + 1) * bin].iter().sum()))
+        .collect();
+    let max = bins.iter().flatten().copied().max().unwrap_or(0).max(1) as u64;
+
+    for row in 0..height {
+        let threshold = (height - row) as u64;
+        queue!(term, cursor::MoveTo(x as u16, (y + row) as u16))?;
+        for counts in &bins {
+            let lit = counts.map(|n| (n as u64 * height as u64).div_ceil(max) >= threshold);
+            let color = style::Color::Rgb {
+                r: if lit[0] { 255 } else { 0 },
+                g: if lit[1] { 255 } else { 0 },
+                b: if lit[2] { 255 } else { 0 },
+            };
+            let ch = if lit.contains(&true) { '█' } else { ' ' };
+            queue!(term, style::PrintStyledContent(ch.with(color).on(style::Color::Black)))?;
+        }
+    }
+    Ok(())
+}
+
+fn draw_scrollbar<W: Write>(term: &mut W, (x, y, width, height): (usize, usize, usize, usize), pos: usize, total: usize, view: usize) -> Result<()> {
+    let vertical = width == 1;
+    let len = if vertical { height } else { width };
+    let thumb = ((view * len) / total.max(1)).clamp(1, len);
+    let start = ((pos * len) / total.max(1)).min(len - thumb);
+    for i in 0..len {
+        let on = i >= start && i < start + thumb;
+        let ch = match (vertical, on) {
+            (true, true) => '┃',
+            (true, false) => '│',
+            (false, true) => '━',
+            (false, false) => '─',
+        };
+        let color = if on { style::Color::White } else { style::Color::DarkGrey };
+        let (cx, cy) = if vertical { (x, y + i) } else { (x + i, y) };
+        queue!(term, cursor::MoveTo(cx as u16, cy as u16), style::PrintStyledContent(ch.with(color)))?;
+    }
+    Ok(())
+}
+
+fn ruler_text(start: (usize, usize), end: Option<(usize, usize)>) -> String {
+    let Some(end) = end else {
+        return format!("ruler from {},{}", start.0, start.1);
+    };
+    let (dx, dy) = (end.0 as f32 - start.0 as f32, end.1 as f32 - start.1 as f32);
+    let angle = (-dy).atan2(dx).to_degrees();
+    format!("{},{} to {},{} distance={:.2} angle={:.1}°", start.0, start.1, end.0, end.1, dx.hypot(dy), angle)
+}
+
+fn line_cells(from: (f32, f32), to: (f32, f32), cols: usize, rows: usize) -> Vec<(usize, usize)> {
+    let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().max(1.0) as usize;
+    let mut cells = Vec::new();
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        let (x, y) = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+        if x < 0.0 || y < 0.0 || x >= cols as f32 || y >= rows as f32 {
+            continue;
+        }
+        let cell = (x as usize, y as usize);
+        if cells.last() != Some(&cell) {
+            cells.push(cell);
+        }
+    }
+    cells
+}
 
-    if iwidth < twidth {
-        offset.0 = (twidth - iwidth) / 2;
+fn help_rect(lines: &[(String, &str)], cols: usize, rows: usize) -> (usize, usize, usize, usize) {
+    let keys = lines.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+    let names = lines.iter().map(|(_, name)| name.len()).max().unwrap_or(0);
+    let (width, height) = ((keys + names + 6).min(cols), (lines.len() + 2).min(rows));
+    ((cols - width) / 2, (rows - height) / 2, width, height)
+}
+
+fn draw_help<W: Write>(term: &mut W, (x, y, width, height): (usize, usize, usize, usize), lines: &[(String, &str)]) -> Result<()> {
+    if width < 4 || height < 2 {
+        return Ok(());
+    }
+    let keys = lines.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+    let inner = width - 4;
+    let mut rows = vec![format!("┌{}┐", "─".repeat(width - 2))];
+    for (k, name) in lines.iter().take(height - 2) {
+        let text: String = format!("{:<keys$}  {}", k, name, keys = keys).chars().take(inner).collect();
+        rows.push(format!("│ {:<inner$} │", text, inner = inner));
     }
-    if iheight < theight {
-        offset.1 = (theight - iheight) / 4;
+    rows.push(format!("└{}┘", "─".repeat(width - 2)));
+    for (i, row) in rows.into_iter().enumerate() {
+        queue!(term, cursor::MoveTo(x as u16, (y + i) as u16), style::PrintStyledContent(row.with(style::Color::White).on(style::Color::Black)))?;
+    }
+    Ok(())
+}
+
+fn zoom_text(zoom: (f32, f32)) -> String {
+    if zoom.0 == zoom.1 { format!("{:.2}", zoom.0) } else { format!("{:.2}x{:.2}", zoom.0, zoom.1) }
+}
+
+fn status_text(path: &str, index: usize, count: usize, im: &image::Image, zoom: (f32, f32), speed: f32, options: &image::RenderOptions) -> String {
+    let (width, height) = im.dimensions();
+    let zoom = zoom_text(zoom);
+    let format = im.format_name();
+    let mut text = if count > 1 {
+        let end = if index == 0 { ", first" } else if index + 1 == count { ", last" } else { "" };
+        format!("{} ({}/{}{}) {}x{} {} zoom={}", path, index + 1, count, end, width, height, format, zoom)
+    } else {
+        format!("{} {}x{} {} zoom={}", path, width, height, format, zoom)
+    };
+    if im.frame_count() > 1 {
+        text.push_str(&format!(" speed={:.2}x", speed));
     }
+    if options.brightness != 0 || options.contrast != 1.0 {
+        text.push_str(&format!(" brightness={:+} contrast={:.1}", options.brightness, options.contrast));
+    }
+    if let Some(threshold) = options.threshold {
+        text.push_str(&format!(" threshold={}", threshold));
+    }
+    if let Some(amount) = options.sharpen {
+        text.push_str(&format!(" sharpen={:.2}", amount));
+    }
+    if let Some((center, width)) = im.window() {
+        text.push_str(&format!(" window={:.0} level={:.0}", width, center));
+    }
+    if options.simulation != image::Simulation::None {
+        text.push_str(&format!(" simulate={:?}", options.simulation).to_lowercase());
+    }
+    text
+}
+
+fn save_snapshot(snapshot: &::image::RgbaImage) -> Result<String> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = format!("tim-{}.png", secs);
+    snapshot.save(&path)?;
+    Ok(path)
+}
 
-    loop {
-        im.draw(term, pos, offset, zoom)?;
+#[cfg(feature = "clipboard")]
+fn copy_color(pix: tim::Pixel) -> Result<()> {
+    clipboard::copy(&pix.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_color(_: tim::Pixel) -> Result<()> {
+    bail!("clipboard support is not enabled")
+}
+
+fn ui_loop<W: Write>(term: &mut W, gallery: &mut gallery::Gallery, view: args::View, protocol: image::Protocol, mut options: image::RenderOptions, playback: args::Playback, watch: bool) -> Result<()> {
+    let slideshow = playback.slideshow;
+    let mut speed = playback.speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1);
+    let mut zoom = (1.0, 1.0);
+    let mut pos = (0, 0);
+    let mut focus = (0.5, 0.5);
+    let sharpen = options.sharpen.unwrap_or(image::DEFAULT_SHARPEN);
+    let mut initial = Some((view.zoom, view.pan));
+    let mut cache = image::RenderCache::default();
+    let mut playing = false;
+    let mut next_frame = Instant::now();
+    let mut slideshow_paused = false;
+    let mut next_slide = Instant::now();
+    let mut ken_burns = None;
+    let mut next_tick = Instant::now();
+    let mut reset = true;
+    let mut show_info = false;
+    let mut message: Option<String> = None;
+    let mut pending_key = None;
+    let mut key_deadline = Instant::now();
+    let mut repeat: Option<usize> = None;
+    let keymap = keys::Keymap::load()?;
+    let help = keymap.help();
+    let mut show_help = false;
+    let mut show_histogram = false;
+    let mut picking = false;
+    let mut ruler: Option<(_, Option<_>)> = None;
+    let mut watcher = None;
+    let mut next_watch = Instant::now();
+    let mut histogram = None;
+    let mut drag = None;
+    let mut click = None;
+    let mut tsize = image::terminal_size()?;
+
+    'ui: loop {
+        options.reserved_rows = if show_info || picking || ruler.is_some() || message.is_some() || repeat.is_some() { 1 } else { 0 };
+        let twidth = tsize.0;
+        let theight = tsize.1.saturating_sub(options.reserved_rows) * 2;
+        gallery.current()?;
+        gallery.preload_next();
+        let title = format!("{} ({}/{})", gallery.path(), gallery.index() + 1, gallery.len());
+        let (path, index, count) = (gallery.path().to_string(), gallery.index(), gallery.len());
+        let im = gallery.current()?;
+
+        if reset {
+            reset = false;
+            let (initial_zoom, initial_pan) = initial.take().unwrap_or_default();
+            (zoom, pos) = match initial_zoom {
+                Some(initial_zoom) => (clamp_zoom((initial_zoom, initial_zoom), twidth, theight), (0, 0)),
+                None => fit_view(im, twidth, theight, view.fit),
+            };
+            if let Some(pan) = initial_pan {
+                pos = im.view_pos(pan, zoom);
+            }
+            focus = view_center(im, zoom, pos, twidth, theight);
+            playing = im.frame_count() > 1;
+            next_frame = Instant::now() + im.frame_delay().div_f32(speed);
+            next_slide = Instant::now() + slideshow.unwrap_or_default();
+            ken_burns = (playback.ken_burns && slideshow.is_some()).then(|| ken_burns::KenBurns::new(zoom));
+            watcher = watch.then(|| watch::Watcher::new(&path));
+            ruler = None;
+            execute!(term, terminal::SetTitle(title), terminal::Clear(terminal::ClearType::All))?;
+            cache.force_full();
+        }
+
+        let (iwidth, iheight) = im.size(zoom);
+        let offset = im.center(zoom, twidth, theight);
+        pos.0 = pos.0.min(iwidth.saturating_sub(twidth));
+        pos.1 = pos.1.min(iheight.saturating_sub(theight));
+
+        let overlay = (show_histogram && twidth > HISTOGRAM_SIZE.0 && theight / 2 > HISTOGRAM_SIZE.1)
+            .then(|| (twidth - HISTOGRAM_SIZE.0 - 1, 1, HISTOGRAM_SIZE.0, HISTOGRAM_SIZE.1));
+        let center = (twidth / 2, theight / 2);
+        let centered = im.pick((center.0.saturating_sub(offset.0) + pos.0, center.1.saturating_sub(offset.1) + pos.1), zoom);
+        let picked = centered.filter(|_| picking);
+        let to_screen = |point| {
+            let (x, y) = im.locate(point, zoom);
+            (x - pos.0 as f32 + offset.0 as f32, (y - pos.1 as f32 + offset.1 as f32) / 2.0)
+        };
+        let (ruler_line, ruler_ends) = match ruler {
+            Some((start, end)) => {
+                let (from, to) = (to_screen(start), to_screen(end.unwrap_or(start)));
+                let ends = [from, to].map(|(x, y)| (x >= 0.0 && y >= 0.0).then_some((x as usize, y as usize)));
+                (line_cells(from, to, twidth, theight / 2), ends)
+            },
+            None => (Vec::new(), [None; 2]),
+        };
+        let crosshair = picking.then_some((center.0, center.1 / 2, 1, 1));
+        let vscroll = (iheight > theight && twidth > 0 && theight >= 2).then(|| (twidth - 1, 0, 1, theight / 2));
+        let hscroll = (iwidth > twidth && theight >= 2).then(|| (0, (theight / 2) - 1, twidth, 1));
+        let help_panel = show_help.then(|| help_rect(&help, twidth, theight / 2));
+        cache.set_overlays(overlay.into_iter().chain(crosshair).chain(vscroll).chain(hscroll).chain(help_panel).chain(ruler_line.iter().map(|&(x, y)| (x, y, 1, 1))).collect());
+
+        match protocol {
+            image::Protocol::Blocks => im.draw(term, &mut cache, &options, (twidth, theight / 2), pos, zoom)?,
+            image::Protocol::Kitty => im.draw_kitty(term, &mut cache, &options, (twidth, theight / 2), pos, zoom)?,
+            image::Protocol::Sixel => im.draw_sixel(term, &mut cache, &options, (twidth, theight / 2), pos, zoom)?,
+            image::Protocol::Iterm2 => im.draw_iterm2(term, &mut cache, &options, (twidth, theight / 2), pos, zoom)?,
+        }
+        if let Some(message) = &message {
+            draw_status(term, twidth, tsize.1.saturating_sub(1), message)?;
+        } else if let Some(repeat) = repeat {
+            draw_status(term, twidth, tsize.1.saturating_sub(1), &repeat.to_string())?;
+        } else if picking {
+            let text = match picked {
+                Some(((x, y), pix)) => format!("{},{} rgb({}, {}, {}) {}", x, y, pix.r, pix.g, pix.b, pix),
+                None => "no pixel under the crosshair".into(),
+            };
+            draw_status(term, twidth, tsize.1.saturating_sub(1), &text)?;
+        } else if let Some((start, end)) = ruler {
+            draw_status(term, twidth, tsize.1.saturating_sub(1), &ruler_text(start, end))?;
+        } else if show_info {
+            draw_status(term, twidth, tsize.1.saturating_sub(1), &status_text(&path, index, count, im, zoom, speed, &options))?;
+        }
+        for &(x, y) in &ruler_line {
+            let ch = if ruler_ends.contains(&Some((x, y))) { '+' } else { '·' };
+            queue!(term, cursor::MoveTo(x as u16, y as u16), style::PrintStyledContent(ch.with(style::Color::Yellow).on(style::Color::Black)))?;
+        }
+        if let Some((x, y, _, _)) = crosshair {
+            let (fg, bg) = match picked {
+                Some((_, pix)) => (if pix.luminance() > 127 { style::Color::Black } else { style::Color::White }, style::Color::Rgb { r: pix.r, g: pix.g, b: pix.b }),
+                None => (style::Color::White, style::Color::Black),
+            };
+            queue!(term, cursor::MoveTo(x as u16, y as u16), style::PrintStyledContent('+'.with(fg).on(bg)))?;
+        }
+        if let Some(bar) = vscroll {
+            draw_scrollbar(term, bar, pos.1, iheight, theight)?;
+        }
+        if let Some(bar) = hscroll {
+            draw_scrollbar(term, bar, pos.0, iwidth, twidth)?;
+        }
+        if let Some((x, y, _, _)) = overlay {
+            let key = (index, im.frame());
+            let hist = match &histogram {
+                Some((k, hist)) if *k == key => hist,
+                _ => &histogram.insert((key, im.histogram())).1,
+            };
+            draw_histogram(term, hist, x, y)?;
+        }
+        if let Some(rect) = help_panel {
+            draw_help(term, rect, &help)?;
+        }
         term.flush()?;
 
-        match event::read()? {
-            Event::Key(key) => {
-                if key.kind == KeyEventKind::Press {
-                    if key.code == KeyCode::Char('q') || key.code == KeyCode::Esc {
-                        break;
-                    } else if key.code == KeyCode::Char('+') || key.code == KeyCode::Char('=') {
-                        zoom += 0.01;
-                    } else if key.code == KeyCode::Char('-') || key.code == KeyCode::Char('_') {
-                        zoom -= 0.01;
-                        if zoom < 0.01 {
-                            zoom = 0.01;
-                        }
-                    } else if key.code == KeyCode::Char('h') || key.code == KeyCode::Char('a') {
-                        if pos.0 > 0 {
-                            pos.0 -= 1;
-                        }
-                    } else if key.code == KeyCode::Char('l') || key.code == KeyCode::Char('d') {
-                        pos.0 += 1;
-                    } else if key.code == KeyCode::Char('k') || key.code == KeyCode::Char('w') {
-                        pos.1 += 1;
-                    } else if key.code == KeyCode::Char('j') || key.code == KeyCode::Char('s') {
-                        if pos.1 > 0 {
-                            pos.1 -= 1;
-                        }
-                    } else if key.code == KeyCode::Char(' ') {
-                        zoom = 1.0;
-                        offset = (0, 0);
-                        pos = (0, 0);
-                        let (iwidth, iheight) = im.size(zoom);
-                        if iwidth > twidth || iheight > theight {
-                            let z1 = (twidth as f32) / (iwidth as f32);
-                            let z2 = (theight as f32) / (iheight as f32);
-                            zoom = if z1 < z2 { z1 } else { z2 };
+        let sliding = slideshow.is_some() && !slideshow_paused;
+        let deadline = [
+            playing.then_some(next_frame),
+            sliding.then_some(next_slide),
+            ken_burns.as_ref().filter(|_| sliding).map(|_| next_tick),
+            watcher.as_ref().map(|_| next_watch),
+            pending_key.map(|_| key_deadline),
+        ].into_iter().flatten().min();
+        let key_expired = pending_key.is_some() && Instant::now() >= key_deadline;
+        if let Some(deadline) = deadline.filter(|_| !key_expired) {
+            let now = Instant::now();
+            if now >= deadline || !event::poll(deadline - now)? {
+                let now = Instant::now();
+                if playing && now >= next_frame {
+                    playing = im.advance();
+                    next_frame = now + im.frame_delay().div_f32(speed);
+                }
+                if let Some(effect) = ken_burns.as_ref().filter(|_| sliding && now >= next_tick) {
+                    let interval = slideshow.unwrap_or_default().as_secs_f32();
+                    let t = 1.0 - (next_slide.saturating_duration_since(now).as_secs_f32() / interval);
+                    (zoom, pos) = effect.frame(im, t, twidth, theight);
+                    next_tick = now + ken_burns::FRAME;
+                }
+                if sliding && now >= next_slide {
+                    reset = gallery.next_wrapping();
+                    next_slide = now + slideshow.unwrap_or_default();
+                }
+                if let Some(watcher) = watcher.as_mut().filter(|_| now >= next_watch) {
+                    if watcher.poll() {
+                        match gallery.reload() {
+                            Ok(()) => {
+                                watcher.loaded();
+                                histogram = None;
+                                cache.force_full();
+                            },
+                            Err(e) => log::write(format_args!("{}: reload failed: {:#}", gallery.path(), e)),
                         }
                     }
+                    next_watch = now + watch::INTERVAL;
                 }
-            },
-            _ => {},
+                continue;
+            }
         }
 
-        let ws = terminal::window_size()?;
-        let twidth = ws.columns as usize;
-        let theight = ws.rows as usize * 2;
-        let (mut iwidth, mut iheight) = im.size(zoom);
+        let mut switch = None;
+        let size = tsize;
+        let mut next = Some(if key_expired {
+            Event::Key(event::KeyCode::Null.into())
+        } else {
+            event::read()?
+        });
+        while let Some(event) = next.take() {
+            let (last_pos, last_zoom) = (pos, zoom);
+            match event {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    if message.take().is_some() {
+                        cache.force_full();
+                    }
+                    if show_help && key.code == event::KeyCode::Esc {
+                        show_help = false;
+                        continue;
+                    }
+                    if let event::KeyCode::Char(c @ '0'..='9') = key.code {
+                        if repeat.is_some() || !keymap.is_bound(key.into()) {
+                            repeat = Some((repeat.unwrap_or(0) * 10 + (c as usize - '0' as usize)).min(MAX_COUNT));
+                            continue;
+                        }
+                    }
+                    let steps = match repeat.take() {
+                        Some(repeat) => {
+                            cache.force_full();
+                            repeat.max(1)
+                        },
+                        None => 1,
+                    };
+                    let (action, again) = keymap.lookup(&mut pending_key, key.into(), |action| match action {
+                        Action::PlayPause | Action::SpeedUp | Action::SpeedDown => im.frame_count() > 1,
+                        Action::CopyColor => picking,
+                        Action::Open => false,
+                        Action::PauseSlideshow => slideshow.is_some(),
+                        Action::WindowWider | Action::WindowNarrower | Action::LevelUp | Action::LevelDown => im.window().is_some(),
+                        _ => true,
+                    });
+                    if again {
+                        next = Some(event);
+                    } else if pending_key.is_some() {
+                        key_deadline = Instant::now() + KEY_TIMEOUT;
+                    }
+                    let action = action.map(|action| match action {
+                        Action::PanUp if view.natural_scroll => Action::PanDown,
+                        Action::PanDown if view.natural_scroll => Action::PanUp,
+                        action => action,
+                    });
+                    match action {
+                        Some(Action::Quit) => break 'ui,
+                        Some(Action::Redraw) => {
+                            execute!(term, terminal::Clear(terminal::ClearType::All))?;
+                            cache.force_full();
+                        },
+                        Some(action @ (Action::ZoomIn | Action::ZoomOut | Action::ZoomInLarge | Action::ZoomOutLarge)) => {
+                            let step = if matches!(action, Action::ZoomInLarge | Action::ZoomOutLarge) { ZOOM_STEP_LARGE } else { ZOOM_STEP };
+                            let mut new_zoom = zoom;
+                            for _ in 0..steps {
+                                new_zoom = if matches!(action, Action::ZoomIn | Action::ZoomInLarge) {
+                                    let max = clamp_zoom((new_zoom.0 * step, new_zoom.1 * step), twidth, theight);
+                                    if max.0 < new_zoom.0 * step {
+                                        message = Some(format!("maximum zoom: {}", zoom_text(max)));
+                                    }
+                                    max
+                                } else {
+                                    ((new_zoom.0 / step).max(MIN_ZOOM), (new_zoom.1 / step).max(MIN_ZOOM))
+                                };
+                            }
+                            pos = center_on(im, new_zoom, focus, twidth, theight);
+                            zoom = new_zoom;
+                        },
+                        Some(Action::ActualSize) => {
+                            pos = center_on(im, (1.0, 1.0), focus, twidth, theight);
+                            zoom = (1.0, 1.0);
+                        },
+                        Some(Action::PanLeft) => pos.0 = pos.0.saturating_sub(steps),
+                        Some(Action::PanRight) => pos.0 += steps,
+                        Some(Action::PanUp) => pos.1 += steps,
+                        Some(Action::PanDown) => pos.1 = pos.1.saturating_sub(steps),
+                        Some(Action::LeftEdge) => pos.0 = 0,
+                        Some(Action::RightEdge) => pos.0 = im.size(zoom).0,
+                        Some(Action::PageUp) => pos.1 = pos.1.saturating_sub(theight * steps),
+                        Some(Action::PageDown) => pos.1 += theight * steps,
+                        Some(Action::Top) => pos.1 = 0,
+                        Some(Action::Bottom) => pos.1 = im.size(zoom).1,
+                        Some(Action::ToggleSampling) => {
+                            im.set_sampling(match im.sampling() {
+                                image::Sampling::Nearest => image::Sampling::Bilinear,
+                                image::Sampling::Bilinear => image::Sampling::Area,
+                                image::Sampling::Area => image::Sampling::Nearest,
+                            });
+                        },
+                        Some(Action::PlayPause) => {
+                            playing = !playing;
+                            if playing {
+                                im.reset_loops();
+                                next_frame = Instant::now() + im.frame_delay().div_f32(speed);
+                            }
+                        },
+                        Some(action @ (Action::SpeedUp | Action::SpeedDown)) => {
+                            speed = if action == Action::SpeedUp { speed * SPEED_STEP } else { speed / SPEED_STEP };
+                            speed = speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1);
+                            if playing {
+                                next_frame = Instant::now() + im.frame_delay().div_f32(speed);
+                            }
+                            message = Some(format!("speed: {:.2}x", speed));
+                            cache.force_full();
+                        },
+                        Some(action @ (Action::NextFrame | Action::PrevFrame)) => {
+                            playing = false;
+                            im.step(action == Action::NextFrame);
+                        },
+                        Some(action @ (Action::RotateLeft | Action::RotateRight)) => {
+                            im.rotate(action == Action::RotateRight);
+                            reset = true;
+                        },
+                        Some(Action::FlipHorizontal) => im.flip_horizontal(),
+                        Some(Action::FlipVertical) => im.flip_vertical(),
+                        Some(action @ (Action::BrightnessUp | Action::BrightnessDown)) => {
+                            let step = if action == Action::BrightnessUp { BRIGHTNESS_STEP } else { -BRIGHTNESS_STEP };
+                            options.brightness = (options.brightness + step).clamp(-255, 255);
+                        },
+                        Some(action @ (Action::ContrastUp | Action::ContrastDown)) => {
+                            let step = if action == Action::ContrastUp { CONTRAST_STEP } else { -CONTRAST_STEP };
+                            options.contrast = (options.contrast + step).clamp(CONTRAST_RANGE.0, CONTRAST_RANGE.1);
+                        },
+                        Some(Action::Grayscale) => options.grayscale = !options.grayscale,
+                        Some(Action::Threshold) => {
+                            options.threshold = match options.threshold {
+                                Some(_) => None,
+                                None => Some(image::DEFAULT_THRESHOLD),
+                            };
+                        },
+                        Some(action @ (Action::WindowWider | Action::WindowNarrower | Action::LevelUp | Action::LevelDown)) => {
+                            if let Some((center, width)) = im.window() {
+                                im.set_window(match action {
+                                    Action::WindowWider => (center, width * WINDOW_STEP),
+                                    Action::WindowNarrower => (center, width / WINDOW_STEP),
+                                    Action::LevelUp => (center + width * LEVEL_STEP, width),
+                                    _ => (center - width * LEVEL_STEP, width),
+                                });
+                                histogram = None;
+                                cache.force_full();
+                            }
+                        },
+                        Some(action @ (Action::ThresholdUp | Action::ThresholdDown)) => {
+                            let threshold = options.threshold.unwrap_or(image::DEFAULT_THRESHOLD);
+                            let threshold = if action == Action::ThresholdUp {
+                                threshold.saturating_add(THRESHOLD_STEP)
+                            } else {
+                                threshold.saturating_sub(THRESHOLD_STEP)
+                            };
+                            options.threshold = Some(threshold);
+                            message = Some(format!("threshold: {}", threshold));
+                            cache.force_full();
+                        },
+                        Some(Action::Histogram) => show_histogram = !show_histogram,
+                        Some(Action::Grid) => options.grid = !options.grid,
+                        Some(Action::Sharpen) => {
+                            options.sharpen = match options.sharpen {
+                                Some(_) => None,
+                                None => Some(sharpen),
+                            };
+                        },
+                        Some(Action::Picker) => {
+                            picking = !picking;
+                            cache.force_full();
+                        },
+                        Some(Action::CopyColor) => {
+                            if let Some((_, pix)) = picked {
+                                message = Some(match copy_color(pix) {
+                                    Ok(()) => format!("copied {}", pix),
+                                    Err(e) => {
+                                        log::write(format_args!("failed to copy {}: {:#}", pix, e));
+                                        format!("failed to copy {}: {:#}", pix, e)
+                                    },
+                                });
+                                cache.force_full();
+                            }
+                        },
+                        Some(Action::RulerStart) => {
+                            ruler = centered.map(|(point, _)| (point, None));
+                            cache.force_full();
+                        },
+                        Some(Action::RulerEnd) => {
+                            if let (Some((start, _)), Some((end, _))) = (ruler, centered) {
+                                ruler = Some((start, Some(end)));
+                            }
+                        },
+                        Some(Action::Invert) => options.invert = !options.invert,
+                        Some(Action::Magenta) => options.magenta = !options.magenta,
+                        Some(Action::Simulate) => {
+                            options.simulation = options.simulation.next();
+                            message = Some(format!("simulate: {:?}", options.simulation).to_lowercase());
+                            cache.force_full();
+                        },
+                        Some(Action::SaveView) => {
+                            let view = (twidth.saturating_sub(offset.0), theight.saturating_sub(offset.1));
+                            message = Some(match save_snapshot(&im.snapshot(&options, pos, zoom, view)) {
+                                Ok(path) => format!("saved {}", path),
+                                Err(e) => {
+                                    log::write(format_args!("failed to save view: {:#}", e));
+                                    format!("failed to save view: {:#}", e)
+                                },
+                            });
+                            cache.force_full();
+                        },
+                        Some(Action::Info) => {
+                            show_info = !show_info;
+                            cache.force_full();
+                        },
+                        Some(Action::PauseSlideshow) => {
+                            slideshow_paused = !slideshow_paused;
+                            next_slide = Instant::now() + slideshow.unwrap_or_default();
+                        },
+                        Some(Action::NextImage) => switch = Some(true),
+                        Some(Action::PrevImage) => switch = Some(false),
+                        Some(Action::Reset) => {
+                            (zoom, pos) = fit_view(im, twidth, theight, view.fit);
+                            focus = view_center(im, zoom, pos, twidth, theight);
+                            options.brightness = 0;
+                            options.contrast = 1.0;
+                            options.invert = false;
+                            if ruler.take().is_some() {
+                                cache.force_full();
+                            }
+                        },
+                        Some(Action::Help) => show_help = !show_help,
+                        Some(Action::ToggleSync | Action::SwitchSide | Action::Open) | None => {},
+                    }
+                },
+                Event::Mouse(mouse) => {
+                    match mouse.kind {
+                        MouseEventKind::Down(MouseButton::Left) => {
+                            drag = Some((mouse.column, mouse.row));
+                            click = drag;
+                        },
+                        MouseEventKind::Drag(MouseButton::Left) => {
+                            if let Some((col, row)) = drag {
+                                let dx = mouse.column as isize - col as isize;
+                                let dy = (mouse.row as isize - row as isize) * 2;
+                                pos.0 = pos.0.saturating_add_signed(-dx);
+                                pos.1 = pos.1.saturating_add_signed(-dy);
+                            }
+                            drag = Some((mouse.column, mouse.row));
+                            if click.is_some_and(|(col, row): (u16, u16)| col.abs_diff(mouse.column) > CLICK_DISTANCE || row.abs_diff(mouse.row) > CLICK_DISTANCE) {
+                                click = None;
+                            }
+                        },
+                        MouseEventKind::Up(MouseButton::Left) => {
+                            drag = None;
+                            if let Some((col, row)) = click.take() {
+                                let (iwidth, iheight) = im.size(zoom);
+                                let x = (col as usize).checked_sub(offset.0).map(|x| x + pos.0).filter(|&x| x < iwidth);
+                                let y = (row as usize * 2).checked_sub(offset.1).map(|y| y + pos.1).filter(|&y| y < iheight);
+                                if let (Some(x), Some(y)) = (x, y) {
+                                    focus = ((x as f32 + 0.5) / iwidth as f32, (y as f32 + 1.0) / iheight as f32);
+                                    pos = center_on(im, zoom, focus, twidth, theight);
+                                }
+                            }
+                        },
+                        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                            let new_zoom = if mouse.kind == MouseEventKind::ScrollUp {
+                                let max = clamp_zoom((zoom.0 * ZOOM_STEP, zoom.1 * ZOOM_STEP), twidth, theight);
+                                if max.0 < zoom.0 * ZOOM_STEP {
+                                    message = Some(format!("maximum zoom: {}", zoom_text(max)));
+                                }
+                                max
+                            } else {
+                                ((zoom.0 / ZOOM_STEP).max(MIN_ZOOM), (zoom.1 / ZOOM_STEP).max(MIN_ZOOM))
+                            };
+                            let anchor = (
+                                (mouse.column as usize).saturating_sub(offset.0) as f32,
+                                ((mouse.row as usize) * 2).saturating_sub(offset.1) as f32,
+                            );
+                            pos = zoom_at(zoom, new_zoom, pos, anchor, anchor);
+                            zoom = new_zoom;
+                            focus = view_center(im, zoom, pos, twidth, theight);
+                        },
+                        _ => {},
+                    }
+                },
+                Event::Resize(cols, rows) => {
+                    tsize = image::usable_size(cols as usize, rows as usize);
+                    execute!(term, terminal::Clear(terminal::ClearType::All))?;
+                    cache.force_full();
+                },
+                _ => {},
+            }
 
-        if iwidth < twidth {
-            pos.0 = 0;
-            offset.0 = (twidth - iwidth) / 2;
+            let (iwidth, iheight) = im.size(zoom);
+            pos.0 = pos.0.min(iwidth.saturating_sub(twidth));
+            pos.1 = pos.1.min(iheight.saturating_sub(theight));
+            if pos != last_pos && zoom == last_zoom {
+                focus = view_center(im, zoom, pos, twidth, theight);
+            }
+            if pos != last_pos || zoom != last_zoom {
+                ken_burns = None;
+            }
+            if next.is_none() && switch.is_none() && !reset && tsize == size && event::poll(Duration::ZERO)? {
+                next = Some(event::read()?);
+            }
         }
-        if iheight < theight {
-            pos.1 = 0;
-            offset.1 = (theight - iheight) / 4;
+
+        if let Some(forward) = switch {
+            reset = gallery.step(forward);
+            if !reset && gallery.len() > 1 {
+                message = Some(if forward { "already at the last image" } else { "already at the first image" }.into());
+                cache.force_full();
+            }
         }
     }
 
     Ok(())
 }
 
+#[cfg(unix)]
+static SIGNALS: Mutex<Option<signal_hook::iterator::Handle>> = Mutex::new(None);
+
+#[cfg(unix)]
+fn init_signal_handler() -> Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    *SIGNALS.lock().unwrap_or_else(|e| e.into_inner()) = Some(signals.handle());
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            let _ = restore_tui();
+            std::process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn remove_signal_handler() {
+    if let Some(handle) = SIGNALS.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        handle.close();
+    }
+}
+
 fn init_panic_hook() {
     let orig_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
@@ -111,50 +794,99 @@ fn init_panic_hook() {
     }));
 }
 
-fn init_tui() -> Result<()> {
+static ALT_SCREEN: AtomicBool = AtomicBool::new(true);
+
+fn enter_screen<W: Write>(out: &mut W) -> std::io::Result<()> {
+    if ALT_SCREEN.load(Ordering::Relaxed) {
+        execute!(out, terminal::EnterAlternateScreen)
+    } else {
+        let (_, rows) = terminal::size()?;
+        execute!(out, style::Print("\n".repeat(rows as usize)))
+    }
+}
+
+fn leave_screen<W: Write>(out: &mut W) -> std::io::Result<()> {
+    if ALT_SCREEN.load(Ordering::Relaxed) {
+        execute!(out, terminal::LeaveAlternateScreen)
+    } else {
+        let (_, rows) = terminal::size()?;
+        execute!(out, cursor::MoveTo(0, rows.saturating_sub(1)), style::Print("\r\n"))
+    }
+}
+
+fn init_tui(alt_screen: bool) -> Result<()> {
     let mut stdout = std::io::stdout();
 
-    execute!(stdout, terminal::EnterAlternateScreen)?;
+    ALT_SCREEN.store(alt_screen, Ordering::Relaxed);
+    enter_screen(&mut stdout)?;
 
     if let Err(e) = terminal::enable_raw_mode() {
-        let _ = execute!(stdout, terminal::LeaveAlternateScreen);
+        let _ = leave_screen(&mut stdout);
         return Err(e.into());
     }
 
-    if let Err(e) = execute!(stdout, cursor::Hide) {
+    if let Err(e) = execute!(stdout, cursor::Hide, event::EnableMouseCapture) {
         let _ = terminal::disable_raw_mode();
-        let _ = execute!(stdout, terminal::LeaveAlternateScreen);
+        let _ = leave_screen(&mut stdout);
         return Err(e.into());
     }
 
     init_panic_hook();
 
+    #[cfg(unix)]
+    if let Err(e) = init_signal_handler() {
+        let _ = restore_tui();
+        return Err(e);
+    }
+
     Ok(())
 }
 
 fn restore_tui() -> Result<()> {
     let mut stdout = std::io::stdout();
 
-    if let Err(e) = execute!(stdout, cursor::Show) {
+    if let Err(e) = execute!(stdout, event::DisableMouseCapture, cursor::Show) {
         let _ = terminal::disable_raw_mode();
-        let _ = execute!(stdout, terminal::LeaveAlternateScreen);
+        let _ = leave_screen(&mut stdout);
         return Err(e.into());
     }
 
     if let Err(e) = terminal::disable_raw_mode() {
-        let _ = execute!(stdout, terminal::LeaveAlternateScreen);
+        let _ = leave_screen(&mut stdout);
         return Err(e.into());
     }
 
-    execute!(stdout, terminal::LeaveAlternateScreen)?;
+    leave_screen(&mut stdout)?;
 
     Ok(())
 }
 
-fn ui(im: &image::Image) -> Result<()> {
-    init_tui()?;
+fn ui(gallery: &mut gallery::Gallery, view: args::View, protocol: image::Protocol, options: image::RenderOptions, playback: args::Playback, watch: bool, alt_screen: bool) -> Result<()> {
+    init_tui(alt_screen)?;
 
-    if let Err(e) = ui_loop(&mut std::io::stdout(), im) {
+    let result = match view.sheet {
+        _ if view.compare => compare::run(&mut std::io::stdout(), gallery, &options),
+        Some(columns) => {
+            let mut selected = 0;
+            let mut result = Ok(());
+            while result.is_ok() {
+                match sheet::run(&mut std::io::stdout(), gallery, &options, columns, &mut selected) {
+                    Ok(true) => {
+                        gallery.set_index(selected);
+                        result = ui_loop(&mut std::io::stdout(), gallery, view, protocol, options, playback, watch);
+                        selected = gallery.index();
+                    },
+                    Ok(false) => break,
+                    Err(e) => result = Err(e),
+                }
+            }
+            result
+        },
+        None => ui_loop(&mut std::io::stdout(), gallery, view, protocol, options, playback, watch),
+    };
+    #[cfg(unix)]
+    remove_signal_handler();
+    if let Err(e) = result {
         let _ = restore_tui();
         return Err(e);
     }
@@ -162,12 +894,226 @@ fn ui(im: &image::Image) -> Result<()> {
     restore_tui()
 }
 
+fn file_url(path: &str) -> Option<String> {
+    if path.contains("://") {
+        return Some(path.to_string());
+    }
+    let path = std::fs::canonicalize(path).ok()?;
+    let mut url = format!("file://{}", std::env::var("HOSTNAME").unwrap_or_default());
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    Some(url)
+}
+
+fn caption(path: &str, links: bool) -> String {
+    let name = path.chars().filter(|c| !c.is_control()).collect::<String>();
+    match file_url(path).filter(|_| links) {
+        Some(url) => format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\\n", url, name),
+        None => format!("{}\n", name),
+    }
+}
+
+fn print(gallery: &mut gallery::Gallery, options: &image::RenderOptions, view: args::View, size: (Option<usize>, Option<usize>), grid: Option<(usize, usize)>, links: Option<bool>) -> Result<()> {
+    let cols = size.0.unwrap_or_else(|| image::terminal_size().map_or(image::DEFAULT_TERMINAL_SIZE.0, |(cols, _)| cols));
+    let mut stdout = std::io::stdout();
+    let mut printed = false;
+    for index in 0..gallery.len() {
+        let path = gallery.paths()[index].clone();
+        if let Some(im) = gallery.get(index) {
+            let text = match (view.zoom, view.pan, size) {
+                _ if grid.is_some() => {
+                    let (cols, rows) = grid.expect("grid size is set");
+                    im.to_ansi_string_grid(cols, rows, options)
+                },
+                (None, None, (None, None)) => im.to_ansi_string_fit_width(cols, options),
+                (None, None, (width, height)) => im.to_ansi_string_sized(width, height, options),
+                (zoom, pan, _) => im.to_ansi_string_at(cols, options, pan.unwrap_or_default(), zoom.map_or((1.0, 1.0), |zoom| (zoom, zoom))),
+            };
+            stdout.write_all(text.as_bytes())?;
+            if let Some(links) = links {
+                stdout.write_all(caption(&path, links).as_bytes())?;
+            }
+            printed = true;
+        }
+    }
+    stdout.flush()?;
+    if !printed {
+        bail!("none of the images could be loaded");
+    }
+    Ok(())
+}
+
+fn palette(gallery: &mut gallery::Gallery, colors: usize) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    let mut printed = false;
+    let many = gallery.len() > 1;
+    for index in 0..gallery.len() {
+        let path = gallery.paths()[index].clone();
+        if let Some(im) = gallery.get(index) {
+            if many {
+                writeln!(stdout, "{}:", path)?;
+            }
+            for (pix, coverage) in im.palette(colors) {
+                writeln!(stdout, "{} {:.2}%", pix, coverage * 100.0)?;
+            }
+            printed = true;
+        }
+    }
+    if !printed {
+        bail!("none of the images could be loaded");
+    }
+    Ok(())
+}
+
+fn diff(a: &str, b: &str, options: &image::LoadOptions) -> Result<(String, image::Image)> {
+    let left = gallery::load(a, options, false).map_err(|e| anyhow!("{}: {:#}", a, e))?;
+    let right = gallery::load(b, options, false).map_err(|e| anyhow!("{}: {:#}", b, e))?;
+    let (im, same) = left.diff(&right);
+    let mut label = format!("{} vs {} ({:.2}% identical", a, b, same * 100.0);
+    if left.dimensions() != right.dimensions() {
+        let (l, r) = (left.dimensions(), right.dimensions());
+        label.push_str(&format!(", sizes differ: {}x{} vs {}x{}", l.0, l.1, r.0, r.1));
+    }
+    label.push(')');
+    Ok((label, im))
+}
+
 fn main() -> Result<()> {
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() < 2 {
-        println!("Usage: {} <file>", args[0]);
+    let mut args = std::env::args();
+    let prog = args.next().unwrap_or_else(|| "tim".into());
+    let args = args::Args::parse(args)?;
+    if args.paths.is_empty() && args.diff.is_none() {
+        println!("Usage: {} [OPTIONS] <file | directory | url | - | --clipboard>...", prog);
+        println!("       {} [OPTIONS] --diff A B", prog);
+        println!("       {} [OPTIONS] --compare A B", prog);
+        println!();
+        println!("Options:");
+        println!("  --print                      print the images instead of opening the viewer");
+        println!("  --width COLS, --height ROWS  size --print output to that many columns and/or rows");
+        println!("  --size COLSxROWS             make --print output exactly that many cells");
+        println!("  --links                      print each file name under its image as a hyperlink");
+        println!("  --palette N                  print the N dominant colors of each image");
+        println!("  --diff A B                   show where two images differ");
+        println!("  --compare A B                show two images side by side");
+        println!("  --clipboard                  view the image on the clipboard (clipboard feature)");
+        println!("  --recursive                  descend into subdirectories");
+        println!("  --wrap                       wrap around at the first and last image");
+        println!("  --grid COLUMNS               show the images as a contact sheet");
+        println!("  --scale-mode MODE            fit, fill, stretch, center or none (default fit)");
+        println!("  --fit-width, --fit-height    fit the image to the terminal width or height");
+        println!("  --zoom FACTOR                initial zoom factor");
+        println!("  --pan X,Y                    image pixel shown in the top-left corner");
+        println!("  --natural-scroll             swap the up and down pan keys (alias --invert-scroll)");
+        println!("  --protocol PROTOCOL          blocks, kitty, sixel or iterm2");
+        println!("  --color-depth DEPTH          truecolor, 256, 16 or mono (alias --colors)");
+        println!("  --ansi-colors RRGGBB,...     the terminal's 16 palette colors for --color-depth 16");
+        println!("  --charset CHARSET            half, sextant, ascii or braille (alias --mode)");
+        println!("  --keep-color                 color the ascii and braille characters");
+        println!("  --glyph GLYPH                auto, upper or lower half block (default auto)");
+        println!("  --bg RRGGBB|checkerboard     background behind transparent pixels");
+        println!("  --transparency-grid          same as --bg checkerboard");
+        println!("  --pad-color RRGGBB           color around the image (default black or --bg)");
+        println!("  --crop X,Y,W,H               show only that region of each image");
+        println!("  --trim                       crop away transparent borders");
+        println!("  --trim-threshold ALPHA       highest alpha that --trim treats as transparent (default 0)");
+        println!("  --rotate 90|180|270          rotate every image clockwise");
+        println!("  --cell-aspect H/W            height-to-width ratio of a terminal cell (default detected)");
+        println!("  --slideshow SECONDS          advance to the next image after that long");
+        println!("  --ken-burns                  slowly zoom and pan across slideshow images");
+        println!("  --speed FACTOR               animation speed, {} to {} (default 1)", SPEED_RANGE.0, SPEED_RANGE.1);
+        println!("  --max-decode-pixels N        refuse larger images (default {}, 0 for no limit)", image::DEFAULT_MAX_PIXELS);
+        println!("  --cache N                    decoded images kept in memory (default {}, 0 for all)", cache::DEFAULT_CAPACITY);
+        println!("  --preload                    decode the next image in the background");
+        println!("  --fast-jpeg                  decode large JPEGs at reduced scale");
+        println!("  --sampling SAMPLING          nearest, bilinear or area (default nearest)");
+        println!("  --linear                     scale in linear light");
+        println!("  --tone-map TONE_MAP          clamp, normalize or reinhard for HDR images (default clamp)");
+        println!("  --hdr-normalize              same as --tone-map normalize");
+        println!("  --simulate DEFICIENCY        protanopia, deuteranopia or tritanopia");
+        println!("  --threshold 0-255            draw pixels black or white by luminance");
+        println!("  --sharpen AMOUNT             apply an unsharp mask (o toggles {} in the viewer)", image::DEFAULT_SHARPEN);
+        println!("  --format dicom               read the files as DICOM (dicom feature)");
+        println!("  --raw WxH:FORMAT             read the files as headerless pixel data");
+        println!("  --watch                      reload images when they change on disk");
+        println!("  --log PATH                   append load times and errors to PATH");
+        println!("  --no-alt-screen              keep the last view in the scrollback");
+        println!();
+        println!("Press ? in the viewer to list the key bindings, which can be changed in");
+        println!("~/.config/tim/config.toml. See README.md for details.");
         return Ok(())
     }
-    let im = image::Image::open(&args[1])?;
-    ui(&im)
+    let colors = args.colors.unwrap_or_else(color::Colors::detect);
+    let options = image::RenderOptions {
+        colors,
+        ansi16: args.ansi16.unwrap_or(color::ANSI16),
+        padding: args.pad_color.unwrap_or_else(|| args.background.padding()),
+        background: args.background,
+        charset: if colors == color::Colors::Mono { image::Charset::Ascii } else { args.charset },
+        glyph: args.glyph,
+        keep_color: args.keep_color && colors != color::Colors::Mono,
+        simulation: args.simulation,
+        threshold: args.threshold,
+        sharpen: args.sharpen,
+        ..Default::default()
+    };
+    let target = if let Some((cols, rows)) = args.size.filter(|_| args.print) {
+        Some((cols * 2, rows * 4))
+    } else if args.print && args.view.zoom.is_none() && args.view.pan.is_none() {
+        match (args.width, args.height) {
+            (None, None) => Some((image::terminal_size().map_or(image::DEFAULT_TERMINAL_SIZE.0, |(cols, _)| cols) * 2, usize::MAX)),
+            (width, height) => Some((width.map_or(usize::MAX, |w| w * 2), height.map_or(usize::MAX, |h| h * 4))),
+        }
+    } else if args.fast_jpeg {
+        image::terminal_size().ok().map(|(cols, rows)| (cols * 2, rows * 4))
+    } else {
+        None
+    };
+    let load_options = image::LoadOptions {
+        crop: args.crop,
+        cell_aspect: Some(args.cell_aspect.unwrap_or_else(image::detect_cell_aspect)),
+        linear: args.linear,
+        sampling: args.sampling,
+        tone_map: args.tone_map,
+        target,
+        max_pixels: args.max_pixels.unwrap_or(image::DEFAULT_MAX_PIXELS),
+        trim: args.trim,
+        rotate: args.rotate,
+        raw: args.raw,
+        dicom: args.dicom,
+    };
+    if let Some(path) = &args.log {
+        log::open(path)?;
+    }
+    let mut gallery = match &args.diff {
+        Some((a, b)) => {
+            let (label, im) = diff(a, b, &load_options)?;
+            let mut gallery = gallery::Gallery::new(vec![label], load_options, args.cache.unwrap_or(cache::DEFAULT_CAPACITY));
+            gallery.insert(0, im);
+            gallery
+        },
+        None => gallery::Gallery::new(gallery::expand(args.paths, args.recursive)?, load_options, args.cache.unwrap_or(cache::DEFAULT_CAPACITY)),
+    };
+    gallery.set_preload(args.preload);
+    gallery.set_wrap(args.wrap);
+    gallery.set_progress(std::io::stderr().is_terminal());
+    let result = if let Some(colors) = args.palette {
+        palette(&mut gallery, colors)
+    } else if args.print {
+        let links = args.links.then(|| std::io::stdout().is_terminal() && std::env::var("TERM").map_or(true, |term| term != "dumb"));
+        print(&mut gallery, &options, args.view, (args.width, args.height), args.size, links)
+    } else {
+        let loaded = gallery.current().map(|_| ());
+        gallery.set_progress(false);
+        let loaded = if args.view.sheet.is_some() { Ok(()) } else { loaded };
+        loaded.and_then(|_| ui(&mut gallery, args.view, args.protocol.unwrap_or_else(image::Protocol::detect), options, args.playback, args.watch, !args.no_alt_screen))
+    };
+    for (path, e) in gallery.errors() {
+        eprintln!("{}: {:#}", path, e);
+    }
+    result
 }
@@ -0,0 +1,27 @@
+use std::io::{ErrorKind, Read};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+pub fn fetch(url: &str, progress: impl FnMut(usize)) -> Result<Vec<u8>> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", "--"])
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| if e.kind() == ErrorKind::NotFound { anyhow!("loading URLs needs the curl command") } else { anyhow!("failed to run curl: {}", e) })?;
+    let data = crate::gallery::read_all(child.stdout.take().context("curl has no stdout")?, progress)?;
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        pipe.read_to_string(&mut stderr)?;
+    }
+    if !child.wait()?.success() {
+        bail!("failed to fetch {}: {}", url, stderr.trim());
+    }
+    Ok(data)
+}
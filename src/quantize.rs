@@ -0,0 +1,63 @@
+use crate::image::Pixel;
+
+fn channel(pix: &Pixel, c: usize) -> u8 {
+    match c {
+        0 => pix.r,
+        1 => pix.g,
+        _ => pix.b,
+    }
+}
+
+fn widest(pixels: &[Pixel]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let (min, max) = pixels.iter().fold((255, 0), |(min, max), pix| (channel(pix, c).min(min), channel(pix, c).max(max)));
+            (c, max.saturating_sub(min))
+        })
+        .max_by_key(|&(c, range)| (range, std::cmp::Reverse(c)))
+        .unwrap_or((0, 0))
+}
+
+fn average(pixels: &[Pixel]) -> Pixel {
+    let n = pixels.len().max(1) as u64;
+    let sum = pixels.iter().fold([0u64; 3], |sum, pix| [sum[0] + pix.r as u64, sum[1] + pix.g as u64, sum[2] + pix.b as u64]);
+    let [r, g, b] = sum.map(|s| ((s + (n / 2)) / n) as u8);
+    Pixel { r, g, b, a: 255 }
+}
+
+pub fn median_cut(pixels: &[Pixel], colors: usize) -> Vec<(Pixel, usize)> {
+    if pixels.is_empty() || colors == 0 {
+        return Vec::new();
+    }
+
+    let mut pixels = pixels.to_vec();
+    let mut boxes = Vec::new();
+    boxes.push(0..pixels.len());
+    while boxes.len() < colors {
+        let Some((i, c)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, range)| (i, widest(&pixels[range.clone()])))
+            .filter(|(_, (_, range))| *range > 0)
+            .max_by_key(|&(i, (_, range))| (range, std::cmp::Reverse(i)))
+            .map(|(i, (c, _))| (i, c))
+        else {
+            break;
+        };
+        let range = boxes[i].clone();
+        let slice = &mut pixels[range.clone()];
+        slice.sort_by_key(|pix| (channel(pix, c), pix.r, pix.g, pix.b));
+        let median = channel(&slice[slice.len() / 2], c);
+        let split = match slice.iter().position(|pix| channel(pix, c) >= median) {
+            Some(0) => slice.iter().position(|pix| channel(pix, c) > median).unwrap_or(slice.len()),
+            Some(split) => split,
+            None => slice.len(),
+        };
+        boxes[i] = range.start..range.start + split;
+        boxes.push(range.start + split..range.end);
+    }
+
+    let mut palette: Vec<(Pixel, usize)> = boxes.into_iter().map(|range| (average(&pixels[range.clone()]), range.len())).collect();
+    palette.sort_by_key(|(pix, count)| (std::cmp::Reverse(*count), pix.r, pix.g, pix.b));
+    palette
+}
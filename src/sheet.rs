@@ -0,0 +1,120 @@
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyEventKind},
+    style::{self, Stylize},
+    terminal,
+    execute,
+    queue,
+};
+
+use tim::image::{self, RenderOptions};
+
+use crate::gallery::Gallery;
+use crate::keys::{Action, Keymap};
+
+struct Layout {
+    columns: usize,
+    cell: (usize, usize),
+    thumb: (usize, usize),
+    visible: usize,
+}
+
+impl Layout {
+    fn new(columns: usize, cols: usize, rows: usize) -> Self {
+        let columns = columns.clamp(1, cols.max(1));
+        let width = (cols / columns).max(1);
+        let thumb = (width.saturating_sub(1).max(1), (width / 2).max(1));
+        let cell = (width, thumb.1 + 2);
+        Self { columns, cell, thumb, visible: (rows / cell.1).max(1) }
+    }
+}
+
+fn caption(path: &str, width: usize) -> String {
+    let name = Path::new(path).file_name().map_or_else(|| path.into(), |name| name.to_string_lossy());
+    let name: String = name.chars().filter(|c| !c.is_control()).take(width).collect();
+    format!("{:<width$}", name, width = width)
+}
+
+pub fn run<W: Write>(term: &mut W, gallery: &mut Gallery, options: &RenderOptions, columns: usize, selected: &mut usize) -> Result<bool> {
+    let keymap = Keymap::load()?;
+    let mut pending_key = None;
+    let mut replay = None;
+    let (cols, rows) = image::terminal_size()?;
+    let mut layout = Layout::new(columns, cols, rows);
+    let mut thumbs: Vec<Option<Vec<String>>> = vec![None; gallery.len()];
+    let mut top = 0;
+    *selected = (*selected).min(gallery.len().saturating_sub(1));
+
+    execute!(term, terminal::SetTitle("tim"))?;
+    loop {
+        let row = *selected / layout.columns;
+        if row < top {
+            top = row;
+        } else if row >= top + layout.visible {
+            top = row + 1 - layout.visible;
+        }
+
+        queue!(term, terminal::Clear(terminal::ClearType::All))?;
+        let first = top * layout.columns;
+        let count = layout.visible * layout.columns;
+        for (index, thumb) in thumbs.iter_mut().enumerate().skip(first).take(count) {
+            let x = (index % layout.columns) * layout.cell.0;
+            let y = ((index / layout.columns) - top) * layout.cell.1;
+            let lines = thumb.get_or_insert_with(|| match gallery.get(index) {
+                Some(im) => im.to_ansi_string_sized(Some(layout.thumb.0), Some(layout.thumb.1), options).lines().map(str::to_string).collect(),
+                None => vec!["(failed to load)".chars().take(layout.thumb.0).collect()],
+            });
+            for (i, line) in lines.iter().enumerate() {
+                queue!(term, cursor::MoveTo(x as u16, (y + i) as u16), style::Print(line))?;
+            }
+            let text = caption(&gallery.paths()[index], layout.thumb.0);
+            let text = if index == *selected { text.reverse() } else { text.stylize() };
+            queue!(term, cursor::MoveTo(x as u16, (y + layout.thumb.1) as u16), style::PrintStyledContent(text))?;
+        }
+        term.flush()?;
+
+        loop {
+            let event = match replay.take() {
+                Some(event) => event,
+                None => event::read()?,
+            };
+            match event {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    let last = gallery.len().saturating_sub(1);
+                    let page = layout.columns * layout.visible;
+                    let (action, again) = keymap.lookup(&mut pending_key, key.into(), |action| matches!(action,
+                        Action::Quit | Action::Open | Action::Redraw | Action::PanLeft | Action::PanRight | Action::PanUp | Action::PanDown |
+                        Action::PageUp | Action::PageDown | Action::LeftEdge | Action::RightEdge | Action::Top | Action::Bottom));
+                    if again {
+                        replay = Some(event);
+                    }
+                    match action {
+                        Some(Action::Quit) => return Ok(false),
+                        Some(Action::Open) => return Ok(true),
+                        Some(Action::Redraw) => thumbs = vec![None; gallery.len()],
+                        Some(Action::PanLeft) => *selected = selected.saturating_sub(1),
+                        Some(Action::PanRight) => *selected = (*selected + 1).min(last),
+                        Some(Action::PanUp) => *selected = selected.saturating_sub(layout.columns),
+                        Some(Action::PanDown) => *selected = (*selected + layout.columns).min(last),
+                        Some(Action::PageUp) => *selected = selected.saturating_sub(page),
+                        Some(Action::PageDown) => *selected = (*selected + page).min(last),
+                        Some(Action::LeftEdge | Action::Top) => *selected = 0,
+                        Some(Action::RightEdge | Action::Bottom) => *selected = last,
+                        _ => continue,
+                    }
+                },
+                Event::Resize(cols, rows) => {
+                    let (cols, rows) = image::usable_size(cols as usize, rows as usize);
+                    layout = Layout::new(columns, cols, rows);
+                    thumbs = vec![None; gallery.len()];
+                },
+                _ => continue,
+            }
+            break;
+        }
+    }
+}
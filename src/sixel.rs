@@ -0,0 +1,79 @@
+use std::fmt::Write;
+
+use crate::image::Pixel;
+
+const LEVELS: usize = 6;
+
+fn level(value: u8) -> usize {
+    ((value as usize * (LEVELS - 1)) + 127) / 255
+}
+
+fn index(pix: &Pixel) -> usize {
+    (level(pix.r) * LEVELS * LEVELS) + (level(pix.g) * LEVELS) + level(pix.b)
+}
+
+fn push_run(out: &mut String, ch: char, count: usize) {
+    if count > 3 {
+        let _ = write!(out, "!{}{}", count, ch);
+    } else {
+        for _ in 0..count {
+            out.push(ch);
+        }
+    }
+}
+
+pub fn encode(pixels: &[Pixel], width: usize, height: usize) -> String {
+    let indices: Vec<usize> = pixels.iter().map(index).collect();
+    let mut out = String::new();
+
+    let _ = write!(out, "\x1bPq\"1;1;{};{}", width, height);
+    for i in 0..(LEVELS * LEVELS * LEVELS) {
+        let r = (i / (LEVELS * LEVELS)) * 100 / (LEVELS - 1);
+        let g = ((i / LEVELS) % LEVELS) * 100 / (LEVELS - 1);
+        let b = (i % LEVELS) * 100 / (LEVELS - 1);
+        let _ = write!(out, "#{};2;{};{};{}", i, r, g, b);
+    }
+
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        let mut used = vec![false; LEVELS * LEVELS * LEVELS];
+        for y in band..(band + rows) {
+            for x in 0..width {
+                used[indices[(y * width) + x]] = true;
+            }
+        }
+
+        let mut first = true;
+        for (color, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            let _ = write!(out, "#{}", color);
+
+            let mut run_char = '?';
+            let mut run_len = 0;
+            for x in 0..width {
+                let mut bits = 0u8;
+                for dy in 0..rows {
+                    if indices[((band + dy) * width) + x] == color {
+                        bits |= 1 << dy;
+                    }
+                }
+                let ch = (63 + bits) as char;
+                if ch == run_char {
+                    run_len += 1;
+                } else {
+                    push_run(&mut out, run_char, run_len);
+                    run_char = ch;
+                    run_len = 1;
+                }
+            }
+            push_run(&mut out, run_char, run_len);
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant, SystemTime};
+
+pub const INTERVAL: Duration = Duration::from_millis(250);
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+type Stamp = (SystemTime, u64);
+
+pub struct Watcher {
+    path: String,
+    stamp: Option<Stamp>,
+    pending: Option<(Stamp, Instant)>,
+}
+
+fn stamp(path: &str) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+impl Watcher {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            stamp: stamp(path),
+            pending: None,
+        }
+    }
+
+    pub fn poll(&mut self) -> bool {
+        let current = stamp(&self.path);
+        if current.is_none() || current == self.stamp {
+            self.pending = None;
+            return false;
+        }
+        let current = current.expect("stamp was checked");
+        match self.pending {
+            Some((pending, since)) if pending == current => since.elapsed() >= DEBOUNCE,
+            _ => {
+                self.pending = Some((current, Instant::now()));
+                false
+            },
+        }
+    }
+
+    pub fn loaded(&mut self) {
+        if let Some((stamp, _)) = self.pending.take() {
+            self.stamp = Some(stamp);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use tim::image::LoadOptions;
+use tim::{Image, Pixel};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+fn apng() -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, 2, 2);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_animated(2, 0).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    writer.set_frame_delay(1, 10).unwrap();
+    writer.write_image_data(&RED.repeat(4)).unwrap();
+    writer.set_frame_delay(1, 5).unwrap();
+    writer.set_blend_op(png::BlendOp::Over).unwrap();
+    writer.write_image_data(&[BLUE, CLEAR, CLEAR, CLEAR].concat()).unwrap();
+    writer.finish().unwrap();
+    data
+}
+
+fn rgba([r, g, b, a]: [u8; 4]) -> Pixel {
+    Pixel { r, g, b, a }
+}
+
+#[test]
+fn apng_frames_are_extracted() {
+    let mut im = Image::load(Cursor::new(apng()), &LoadOptions::default()).unwrap();
+    assert_eq!(im.format(), Some(image::ImageFormat::Png));
+    assert_eq!(im.dimensions(), (2, 2));
+    assert_eq!(im.frame_count(), 2);
+    assert_eq!(im.pixel((0, 0), (1.0, 1.0)), rgba(RED));
+    assert_eq!(im.frame_delay(), Duration::from_millis(100));
+
+    im.step(true);
+    assert_eq!(im.frame(), 1);
+    assert_eq!(im.pixel((0, 0), (1.0, 1.0)), rgba(BLUE));
+    assert_eq!(im.pixel((1, 1), (1.0, 1.0)), rgba(RED));
+    assert_eq!(im.frame_delay(), Duration::from_millis(200));
+}
+
+#[test]
+fn apng_loops_forever() {
+    let mut im = Image::load(Cursor::new(apng()), &LoadOptions::default()).unwrap();
+    for _ in 0..10 {
+        assert!(im.advance());
+    }
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::{image, solid};
+
+#[test]
+fn small_image_is_centered_in_middle_cell() {
+    let im = image(solid(4, 2, [255, 0, 0]));
+    let (_, y) = im.center((1.0, 1.0), 4, 3 * 2);
+    assert_eq!(y, 2);
+    assert_eq!(y / 2, 1);
+    assert_eq!(y % 2, 0);
+}
+
+#[test]
+fn center_uses_pixel_rows() {
+    let im = image(solid(2, 4, [255, 0, 0]));
+    assert_eq!(im.center((1.0, 1.0), 10, 20), (4, 8));
+    assert_eq!(im.center((1.0, 1.0), 2, 4), (0, 0));
+    assert_eq!(im.center((1.0, 1.0), 1, 1), (0, 0));
+}
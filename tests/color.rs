@@ -0,0 +1,32 @@
+use tim::color::{self, ANSI16};
+use tim::Pixel;
+
+fn rgb(r: u8, g: u8, b: u8) -> Pixel {
+    Pixel { r, g, b, a: 255 }
+}
+
+#[test]
+fn palette_colors_match_themselves() {
+    for (i, pix) in ANSI16.iter().enumerate() {
+        assert_eq!(color::nearest_ansi16(*pix, &ANSI16), i as u8);
+    }
+}
+
+#[test]
+fn custom_palette_is_used() {
+    let mut palette = ANSI16;
+    palette[4] = rgb(0x26, 0x8b, 0xd2);
+    assert_eq!(color::nearest_ansi16(rgb(0x30, 0x80, 0xd0), &palette), 4);
+}
+
+#[test]
+fn dark_gray_maps_to_black() {
+    assert_eq!(color::nearest_ansi16(rgb(60, 60, 70), &ANSI16), 0);
+}
+
+#[test]
+fn palette_needs_sixteen_colors() {
+    let palette = ANSI16.map(|pix| pix.to_string()).join(",");
+    assert_eq!(color::parse_palette(&palette).unwrap(), ANSI16);
+    assert!(color::parse_palette("000000,ffffff").is_err());
+}
@@ -0,0 +1,32 @@
+#![allow(dead_code)]
+
+use std::io::Cursor;
+
+use tim::image::LoadOptions;
+use tim::Image;
+
+pub fn solid(width: u32, height: u32, rgb: [u8; 3]) -> image::RgbImage {
+    image::RgbImage::from_pixel(width, height, image::Rgb(rgb))
+}
+
+pub fn gradient(width: u32, height: u32) -> image::RgbImage {
+    image::RgbImage::from_fn(width, height, |x, y| image::Rgb([(x * 10) as u8, (y * 10) as u8, 42]))
+}
+
+pub fn gradient32f(width: u32, height: u32) -> image::Rgb32FImage {
+    image::Rgb32FImage::from_fn(width, height, |x, y| image::Rgb([x as f32, y as f32 * 0.25, 0.5]))
+}
+
+pub fn image(im: image::RgbImage) -> Image {
+    Image::new(image::DynamicImage::ImageRgb8(im)).unwrap()
+}
+
+pub fn encode(im: &image::RgbImage, format: image::ImageFormat) -> Vec<u8> {
+    let mut data = Vec::new();
+    im.write_to(&mut Cursor::new(&mut data), format).unwrap();
+    data
+}
+
+pub fn round_trip(im: &image::RgbImage, format: image::ImageFormat) -> Image {
+    Image::load(Cursor::new(encode(im, format)), &LoadOptions::default()).unwrap()
+}
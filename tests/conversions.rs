@@ -0,0 +1,119 @@
+mod common;
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
+use tim::image::LoadOptions;
+use tim::{Image, Pixel};
+
+fn pixels(im: DynamicImage) -> Vec<Pixel> {
+    let (width, height) = (im.width() as usize, im.height() as usize);
+    let im = Image::new(im).unwrap();
+    assert_eq!(im.dimensions(), (width, height));
+    (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|pos| im.pixel(pos, (1.0, 1.0))).collect()
+}
+
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> Pixel {
+    Pixel { r, g, b, a }
+}
+
+#[test]
+fn gray8() {
+    let im = ImageBuffer::from_raw(2, 1, vec![0u8, 200]).map(|im: ImageBuffer<Luma<u8>, _>| DynamicImage::ImageLuma8(im)).unwrap();
+    assert_eq!(pixels(im), [rgba(0, 0, 0, 255), rgba(200, 200, 200, 255)]);
+}
+
+#[test]
+fn grayalpha8_keeps_alpha() {
+    let im = ImageBuffer::from_raw(2, 1, vec![100u8, 0, 50, 128]).map(|im: ImageBuffer<LumaA<u8>, _>| DynamicImage::ImageLumaA8(im)).unwrap();
+    assert_eq!(pixels(im), [rgba(100, 100, 100, 0), rgba(50, 50, 50, 128)]);
+}
+
+#[test]
+fn rgb8() {
+    let im = DynamicImage::ImageRgb8(ImageBuffer::from_fn(2, 2, |x, y| Rgb([x as u8, y as u8, 7])));
+    assert_eq!(pixels(im), [rgba(0, 0, 7, 255), rgba(1, 0, 7, 255), rgba(0, 1, 7, 255), rgba(1, 1, 7, 255)]);
+}
+
+#[test]
+fn rgba8_keeps_straight_alpha() {
+    let im = DynamicImage::ImageRgba8(ImageBuffer::from_raw(2, 1, vec![255u8, 0, 0, 0, 10, 20, 30, 64]).unwrap());
+    assert_eq!(pixels(im), [rgba(255, 0, 0, 0), rgba(10, 20, 30, 64)]);
+}
+
+#[test]
+fn gray16_keeps_high_byte() {
+    let im = ImageBuffer::from_raw(3, 1, vec![0u16, 0x12ff, 0xffff]).map(|im: ImageBuffer<Luma<u16>, _>| DynamicImage::ImageLuma16(im)).unwrap();
+    assert_eq!(pixels(im), [rgba(0, 0, 0, 255), rgba(0x12, 0x12, 0x12, 255), rgba(255, 255, 255, 255)]);
+}
+
+#[test]
+fn grayalpha16() {
+    let im = ImageBuffer::from_raw(1, 1, vec![0x8000u16, 0x40ff]).map(|im: ImageBuffer<LumaA<u16>, _>| DynamicImage::ImageLumaA16(im)).unwrap();
+    assert_eq!(pixels(im), [rgba(0x80, 0x80, 0x80, 0x40)]);
+}
+
+#[test]
+fn rgb16() {
+    let im = DynamicImage::ImageRgb16(ImageBuffer::from_raw(1, 1, vec![0x0100u16, 0x7fff, 0xff00]).unwrap());
+    assert_eq!(pixels(im), [rgba(1, 0x7f, 0xff, 255)]);
+}
+
+#[test]
+fn rgba16() {
+    let im = DynamicImage::ImageRgba16(ImageBuffer::from_pixel(1, 1, Rgba([0xffffu16, 0, 0x2000, 0x8000])));
+    assert_eq!(pixels(im), [rgba(255, 0, 0x20, 0x80)]);
+}
+
+#[test]
+fn rgb32f_scales_and_clamps() {
+    let im = DynamicImage::ImageRgb32F(ImageBuffer::from_raw(2, 1, vec![0.0f32, 0.5, 1.0, -0.25, 1.5, 0.2]).unwrap());
+    assert_eq!(pixels(im), [rgba(0, 127, 255, 255), rgba(0, 255, 51, 255)]);
+}
+
+#[test]
+fn rgba32f() {
+    let im = DynamicImage::ImageRgba32F(ImageBuffer::from_pixel(1, 1, Rgba([1.0f32, 0.25, 0.0, 0.5])));
+    assert_eq!(pixels(im), [rgba(255, 63, 0, 127)]);
+}
+
+#[test]
+fn bmp_round_trip() {
+    let im = common::round_trip(&common::gradient(3, 2), image::ImageFormat::Bmp);
+    assert_eq!(im.format(), Some(image::ImageFormat::Bmp));
+    assert_eq!(im.pixel((2, 1), (1.0, 1.0)), rgba(20, 10, 42, 255));
+}
+
+#[test]
+fn tga_round_trip() {
+    let path = std::env::temp_dir().join(format!("tim-test-{}.tga", std::process::id()));
+    common::gradient(3, 2).save(&path).unwrap();
+    let im = Image::open(&path, &LoadOptions::default());
+    std::fs::remove_file(&path).unwrap();
+    let im = im.unwrap();
+    assert_eq!(im.format(), Some(image::ImageFormat::Tga));
+    assert_eq!(im.pixel((2, 1), (1.0, 1.0)), rgba(20, 10, 42, 255));
+}
+
+#[test]
+fn bilevel_png() {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, 3, 1);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+    encoder.write_header().unwrap().write_image_data(&[0b1010_0000]).unwrap();
+    let im = Image::load(Cursor::new(data), &LoadOptions::default()).unwrap();
+    assert_eq!(im.dimensions(), (3, 1));
+    assert_eq!(im.pixel((0, 0), (1.0, 1.0)), rgba(255, 255, 255, 255));
+    assert_eq!(im.pixel((1, 0), (1.0, 1.0)), rgba(0, 0, 0, 255));
+    assert_eq!(im.pixel((2, 0), (1.0, 1.0)), rgba(255, 255, 255, 255));
+}
+
+#[test]
+fn rotate_on_load() {
+    let data = common::encode(&common::gradient(3, 2), image::ImageFormat::Png);
+    let im = Image::load(Cursor::new(data), &LoadOptions { rotate: 1, ..Default::default() }).unwrap();
+    assert_eq!(im.size((1.0, 1.0)), (2, 3));
+    assert_eq!(im.pixel((0, 0), (1.0, 1.0)), rgba(0, 10, 42, 255));
+    assert_eq!(im.pixel((1, 2), (1.0, 1.0)), rgba(20, 0, 42, 255));
+}
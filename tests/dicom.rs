@@ -0,0 +1,95 @@
+#![cfg(feature = "dicom")]
+
+use std::io::Cursor;
+
+use tim::image::LoadOptions;
+use tim::{Image, Pixel};
+
+fn element(data: &mut Vec<u8>, (group, elem): (u16, u16), vr: &[u8; 2], value: &[u8]) {
+    data.extend_from_slice(&group.to_le_bytes());
+    data.extend_from_slice(&elem.to_le_bytes());
+    data.extend_from_slice(vr);
+    if matches!(vr, b"OB" | b"OW" | b"SQ") {
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    } else {
+        data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    }
+    data.extend_from_slice(value);
+}
+
+fn delimiter(data: &mut Vec<u8>, elem: u16, len: u32) {
+    data.extend_from_slice(&0xfffeu16.to_le_bytes());
+    data.extend_from_slice(&elem.to_le_bytes());
+    data.extend_from_slice(&len.to_le_bytes());
+}
+
+fn dicom(window: Option<(&[u8], &[u8])>, bits: u16) -> Vec<u8> {
+    let mut data = vec![0; 128];
+    data.extend_from_slice(b"DICM");
+    element(&mut data, (0x0002, 0x0010), b"UI", b"1.2.840.10008.1.2.1\0");
+
+    data.extend_from_slice(&[0x08, 0x00, 0x15, 0x11]);
+    data.extend_from_slice(b"SQ\0\0");
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    delimiter(&mut data, 0xe000, u32::MAX);
+    element(&mut data, (0x0008, 0x1150), b"UI", b"1.2\0");
+    delimiter(&mut data, 0xe00d, 0);
+    delimiter(&mut data, 0xe0dd, 0);
+
+    element(&mut data, (0x0028, 0x0002), b"US", &1u16.to_le_bytes());
+    element(&mut data, (0x0028, 0x0004), b"CS", b"MONOCHROME2 ");
+    element(&mut data, (0x0028, 0x0010), b"US", &1u16.to_le_bytes());
+    element(&mut data, (0x0028, 0x0011), b"US", &3u16.to_le_bytes());
+    element(&mut data, (0x0028, 0x0100), b"US", &bits.to_le_bytes());
+    element(&mut data, (0x0028, 0x0101), b"US", &12u16.to_le_bytes());
+    element(&mut data, (0x0028, 0x0103), b"US", &1u16.to_le_bytes());
+    if let Some((center, width)) = window {
+        element(&mut data, (0x0028, 0x1050), b"DS", center);
+        element(&mut data, (0x0028, 0x1051), b"DS", width);
+    }
+    let pixels: Vec<u8> = [-100i16, 0, 100].iter().flat_map(|v| ((*v as u16) & 0x0fff).to_le_bytes()).collect();
+    element(&mut data, (0x7fe0, 0x0010), b"OW", &pixels);
+    data
+}
+
+fn levels(im: &Image) -> Vec<u8> {
+    (0..3).map(|x| im.pixel((x, 0), (1.0, 1.0)).r).collect()
+}
+
+#[test]
+fn dicom_is_windowed_to_its_value_range() {
+    let im = Image::load(Cursor::new(dicom(None, 16)), &LoadOptions::default()).unwrap();
+    assert_eq!(im.dimensions(), (3, 1));
+    assert_eq!(im.format_name(), "DICOM");
+    assert_eq!(im.window(), Some((0.0, 200.0)));
+    assert_eq!(levels(&im), [0, 128, 255]);
+    assert_eq!(im.pixel((0, 0), (1.0, 1.0)), Pixel { r: 0, g: 0, b: 0, a: 255 });
+}
+
+#[test]
+fn dicom_window_can_be_changed() {
+    let mut im = Image::load(Cursor::new(dicom(Some((b"0 ", b"20\\40 ")), 16)), &LoadOptions::default()).unwrap();
+    assert_eq!(im.window(), Some((0.0, 20.0)));
+    assert_eq!(levels(&im), [0, 134, 255]);
+
+    im.set_window((0.0, 1000.0));
+    assert_eq!(levels(&im), [102, 128, 153]);
+}
+
+#[test]
+fn dicom_with_invalid_bit_depth_is_an_error() {
+    let err = Image::load(Cursor::new(dicom(None, 0)), &LoadOptions::default()).err().unwrap();
+    assert!(err.to_string().contains("bit depth"), "{}", err);
+}
+
+#[test]
+fn dicom_with_deeply_nested_sequences_is_an_error() {
+    let mut data = vec![0; 128];
+    data.extend_from_slice(b"DICM");
+    element(&mut data, (0x0002, 0x0010), b"UI", b"1.2.840.10008.1.2.1\0");
+    for _ in 0..10_000 {
+        delimiter(&mut data, 0xe000, u32::MAX);
+    }
+    assert!(Image::load(Cursor::new(data), &LoadOptions::default()).is_err());
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::{image, solid};
+use tim::Pixel;
+
+const RED: Pixel = Pixel { r: 255, g: 0, b: 0, a: 255 };
+
+#[test]
+fn changed_pixels_are_highlighted() {
+    let a = solid(2, 2, [200, 200, 200]);
+    let mut b = a.clone();
+    b.put_pixel(1, 0, image::Rgb([0, 0, 0]));
+    let (diff, same) = image(a).diff(&image(b));
+    assert_eq!(diff.dimensions(), (2, 2));
+    assert_eq!(same, 0.75);
+    assert_eq!(diff.pixel((1, 0), (1.0, 1.0)), RED);
+    assert_ne!(diff.pixel((0, 0), (1.0, 1.0)), RED);
+}
+
+#[test]
+fn size_mismatch_is_aligned_top_left() {
+    let (diff, same) = image(solid(2, 1, [9, 9, 9])).diff(&image(solid(1, 2, [9, 9, 9])));
+    assert_eq!(diff.dimensions(), (2, 2));
+    assert_eq!(same, 0.25);
+    assert_eq!(diff.pixel((1, 1), (1.0, 1.0)), RED);
+}
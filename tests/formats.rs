@@ -0,0 +1,25 @@
+#![cfg(any(feature = "tiff", feature = "webp"))]
+
+mod common;
+
+use tim::Image;
+
+fn round_trip(format: image::ImageFormat) -> Image {
+    common::round_trip(&common::gradient(7, 5), format)
+}
+
+#[cfg(feature = "tiff")]
+#[test]
+fn tiff_round_trip() {
+    let im = round_trip(image::ImageFormat::Tiff);
+    assert_eq!(im.dimensions(), (7, 5));
+    assert_eq!(im.format(), Some(image::ImageFormat::Tiff));
+}
+
+#[cfg(feature = "webp")]
+#[test]
+fn webp_round_trip() {
+    let im = round_trip(image::ImageFormat::WebP);
+    assert_eq!(im.dimensions(), (7, 5));
+    assert_eq!(im.format(), Some(image::ImageFormat::WebP));
+}
@@ -0,0 +1,58 @@
+use tim::image::{Glyph, RenderCache, RenderOptions};
+use tim::Image;
+
+const TOP: &str = "255;0;0m";
+const BOTTOM: &str = "0;0;255m";
+
+fn cell(glyph: Glyph) -> String {
+    let im = image::RgbImage::from_fn(1, 2, |_, y| image::Rgb(if y == 0 { [255, 0, 0] } else { [0, 0, 255] }));
+    let im = Image::new(image::DynamicImage::ImageRgb8(im)).unwrap();
+    let mut out = Vec::new();
+    im.draw(&mut out, &mut RenderCache::default(), &RenderOptions { glyph, ..Default::default() }, (1, 1), (0, 0), (1.0, 1.0)).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn upper_glyph_draws_the_top_pixel_as_foreground() {
+    let out = cell(Glyph::Upper);
+    assert!(out.contains('▀'));
+    assert!(out.contains(&format!("\x1b[38;2;{}", TOP)));
+    assert!(out.contains(&format!("\x1b[48;2;{}", BOTTOM)));
+}
+
+#[test]
+fn lower_glyph_draws_the_bottom_pixel_as_foreground() {
+    let out = cell(Glyph::Lower);
+    assert!(out.contains('▄'));
+    assert!(out.contains(&format!("\x1b[38;2;{}", BOTTOM)));
+    assert!(out.contains(&format!("\x1b[48;2;{}", TOP)));
+}
+
+#[test]
+fn odd_last_row_keeps_the_upper_glyph() {
+    let im = Image::new(image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1, 3, image::Rgb([255, 0, 0])))).unwrap();
+    let out = im.to_ansi_string_sized(Some(1), None, &RenderOptions { glyph: Glyph::Lower, ..Default::default() });
+    let last = out.lines().last().unwrap();
+    assert!(last.contains('▀'));
+    assert!(last.contains(&format!("\x1b[38;2;{}", TOP)));
+}
+
+fn column(colors: &[[u8; 3]]) -> String {
+    let im = image::RgbImage::from_fn(1, colors.len() as u32, |_, y| image::Rgb(colors[y as usize]));
+    let im = Image::new(image::DynamicImage::ImageRgb8(im)).unwrap();
+    let mut out = Vec::new();
+    im.draw(&mut out, &mut RenderCache::default(), &RenderOptions::default(), (1, colors.len() / 2), (0, 0), (1.0, 1.0)).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn auto_glyph_puts_the_background_next_to_the_matching_pixel() {
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    assert!(column(&[RED, RED, BLUE, RED]).ends_with("\x1b[48;2;255;0;0m\x1b[38;2;0;0;255m▀\x1b[49m\x1b[39m"));
+    assert!(column(&[RED, RED, RED, BLUE]).ends_with("\x1b[48;2;255;0;0m\x1b[38;2;0;0;255m▄\x1b[49m\x1b[39m"));
+    let out = column(&[GREEN, GREEN, RED, BLUE, RED, RED]);
+    assert_eq!(out.matches('▄').count(), 1);
+    assert!(!out.contains('▀'));
+}
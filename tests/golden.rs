@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use tim::image::{Background, RenderCache, RenderOptions};
+use tim::{Image, Pixel};
+
+fn render(im: &Image, options: &RenderOptions, (cols, rows): (usize, usize)) -> Vec<u8> {
+    let (width, height) = im.dimensions();
+    let z = (cols as f32 / width as f32).min((rows * 2) as f32 / height as f32);
+    let mut out = Vec::new();
+    im.draw(&mut out, &mut RenderCache::default(), options, (cols, rows), (0, 0), (z, z)).unwrap();
+    out
+}
+
+fn check(name: &str, out: &[u8]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, out).unwrap();
+    }
+    let golden = std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", path.display(), e));
+    assert!(out == golden, "{} does not match the rendered output:\n{}", name, String::from_utf8_lossy(out).escape_debug());
+}
+
+fn rgba(width: u32, height: u32, f: impl Fn(u32, u32) -> [u8; 4]) -> Image {
+    Image::new(image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| image::Rgba(f(x, y))))).unwrap()
+}
+
+fn quadrants(x: u32, y: u32) -> [u8; 4] {
+    match (x < 2, y < 2) {
+        (true, true) => [255, 0, 0, 255],
+        (false, true) => [0, 255, 0, 255],
+        (true, false) => [0, 0, 255, 255],
+        (false, false) => [255, 255, 255, 255],
+    }
+}
+
+#[test]
+fn half_blocks() {
+    check("half_blocks.ans", &render(&rgba(4, 4, |x, y| [(x * 60) as u8, (y * 60) as u8, 128, 255]), &RenderOptions::default(), (4, 2)));
+}
+
+#[test]
+fn letterboxed() {
+    let options = RenderOptions { padding: Pixel { r: 16, g: 32, b: 48, a: 255 }, ..Default::default() };
+    check("letterbox_wide.ans", &render(&rgba(4, 4, quadrants), &options, (8, 2)));
+    check("letterbox_tall.ans", &render(&rgba(4, 4, quadrants), &options, (4, 4)));
+}
+
+#[test]
+fn odd_height() {
+    check("odd_height.ans", &render(&rgba(3, 3, |x, y| [(x * 100) as u8, (y * 100) as u8, 50, 255]), &RenderOptions::default(), (3, 2)));
+}
+
+#[test]
+fn alpha_composited() {
+    let im = rgba(4, 2, |x, _| [255, 255, 255, (x * 85) as u8]);
+    let options = RenderOptions { background: Background::Color(Pixel { r: 0, g: 0, b: 255, a: 255 }), ..Default::default() };
+    check("alpha_color.ans", &render(&im, &options, (4, 1)));
+    check("alpha_checkerboard.ans", &render(&rgba(16, 16, |_, _| [255, 0, 0, 128]), &RenderOptions { background: Background::Checkerboard, ..Default::default() }, (16, 8)));
+}
@@ -0,0 +1,36 @@
+use tim::image::RenderOptions;
+use tim::Image;
+
+fn strip(text: &str) -> String {
+    let mut out = String::new();
+    let mut escape = false;
+    for ch in text.chars() {
+        match ch {
+            '\x1b' => escape = true,
+            'm' if escape => escape = false,
+            _ if escape => {},
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn grid(width: u32, height: u32, cols: usize, rows: usize) -> Vec<usize> {
+    let im = Image::new(image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(width, height, image::Rgb([200, 100, 50])))).unwrap();
+    strip(&im.to_ansi_string_grid(cols, rows, &RenderOptions::default())).lines().map(|line| line.chars().count()).collect()
+}
+
+#[test]
+fn grid_output_has_the_requested_size() {
+    assert_eq!(grid(32, 32, 12, 3), [12; 3]);
+    assert_eq!(grid(10, 40, 7, 5), [7; 5]);
+    assert_eq!(grid(40, 10, 3, 9), [3; 9]);
+}
+
+#[test]
+fn grid_is_deterministic() {
+    let im = Image::new(image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(9, 5, |x, y| image::Rgb([(x * 20) as u8, (y * 40) as u8, 7])))).unwrap();
+    let options = RenderOptions::default();
+    assert_eq!(im.to_ansi_string_grid(6, 4, &options), im.to_ansi_string_grid(6, 4, &options));
+    assert!(im.to_ansi_string_grid(0, 4, &options).is_empty());
+}
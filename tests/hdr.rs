@@ -0,0 +1,36 @@
+mod common;
+
+use std::io::Cursor;
+
+use tim::image::{LoadOptions, ToneMap};
+use tim::{Image, Pixel};
+
+fn load(data: Vec<u8>, tone_map: ToneMap) -> Image {
+    Image::load(Cursor::new(data), &LoadOptions { tone_map, ..Default::default() }).unwrap()
+}
+
+#[test]
+fn radiance_hdr() {
+    let mut data = Vec::new();
+    let pixels: Vec<_> = common::gradient32f(6, 4).pixels().copied().collect();
+    image::codecs::hdr::HdrEncoder::new(&mut data).encode(&pixels, 6, 4).unwrap();
+    let im = load(data.clone(), ToneMap::Clamp);
+    assert_eq!(im.dimensions(), (6, 4));
+    assert_eq!(im.format(), Some(image::ImageFormat::Hdr));
+    assert_eq!(im.pixel((5, 0), (1.0, 1.0)).r, 255);
+
+    let im = load(data, ToneMap::Normalize);
+    assert_eq!(im.pixel((0, 0), (1.0, 1.0)), Pixel { r: 0, g: 0, b: 25, a: 255 });
+    assert_eq!(im.pixel((5, 0), (1.0, 1.0)).r, 255);
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn openexr() {
+    let mut data = Vec::new();
+    image::DynamicImage::ImageRgb32F(common::gradient32f(6, 4)).write_to(&mut Cursor::new(&mut data), image::ImageFormat::OpenExr).unwrap();
+    let im = load(data, ToneMap::Reinhard);
+    assert_eq!(im.dimensions(), (6, 4));
+    assert_eq!(im.format(), Some(image::ImageFormat::OpenExr));
+    assert_eq!(im.pixel((1, 0), (1.0, 1.0)).r, 127);
+}
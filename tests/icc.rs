@@ -0,0 +1,79 @@
+#![cfg(feature = "icc")]
+
+use std::io::Cursor;
+
+use tim::image::LoadOptions;
+use tim::Image;
+
+const SRGB_COLORANTS: [(&[u8; 4], [f32; 3]); 3] = [
+    (b"rXYZ", [0.4361, 0.2225, 0.0139]),
+    (b"gXYZ", [0.3851, 0.7169, 0.0971]),
+    (b"bXYZ", [0.1431, 0.0606, 0.7141]),
+];
+
+fn fixed(value: f32) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn profile(curve: &[u8]) -> Vec<u8> {
+    let mut tags: Vec<(&[u8; 4], Vec<u8>)> = SRGB_COLORANTS
+        .iter()
+        .map(|(sig, xyz)| (*sig, [b"XYZ \0\0\0\0".as_slice(), &fixed(xyz[0]), &fixed(xyz[1]), &fixed(xyz[2])].concat()))
+        .collect();
+    for sig in [b"rTRC", b"gTRC", b"bTRC"] {
+        tags.push((sig, curve.to_vec()));
+    }
+
+    let mut header = vec![0; 128];
+    header[16..20].copy_from_slice(b"RGB ");
+    header[20..24].copy_from_slice(b"XYZ ");
+    header[36..40].copy_from_slice(b"acsp");
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut body = Vec::new();
+    let start = header.len() + 4 + (tags.len() * 12);
+    for (sig, data) in &tags {
+        table.extend_from_slice(*sig);
+        table.extend_from_slice(&((start + body.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        body.extend_from_slice(data);
+    }
+    let mut profile = [header, table, body].concat();
+    let size = (profile.len() as u32).to_be_bytes();
+    profile[0..4].copy_from_slice(&size);
+    profile
+}
+
+fn jpeg(gray: u8, profile: Option<&[u8]>) -> Vec<u8> {
+    let mut data = Vec::new();
+    image::RgbImage::from_pixel(8, 8, image::Rgb([gray; 3]))
+        .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Jpeg)
+        .unwrap();
+    if let Some(profile) = profile {
+        let payload = [b"ICC_PROFILE\0\x01\x01".as_slice(), profile].concat();
+        let segment = [[0xff, 0xe2].as_slice(), &((payload.len() + 2) as u16).to_be_bytes(), &payload].concat();
+        data.splice(2..2, segment);
+    }
+    data
+}
+
+fn level(data: Vec<u8>) -> u8 {
+    Image::load(Cursor::new(data), &LoadOptions::default()).unwrap().pixel((0, 0), (1.0, 1.0)).r
+}
+
+#[test]
+fn linear_profile_is_converted_to_srgb() {
+    assert_eq!(level(jpeg(128, Some(&profile(b"curv\0\0\0\0\0\0\0\0")))), 188);
+}
+
+#[test]
+fn gamma_profile_is_converted_to_srgb() {
+    let gamma_18 = [b"curv\0\0\0\0\0\0\0\x01".as_slice(), &[0x01, 0xcd]].concat();
+    let converted = level(jpeg(128, Some(&profile(&gamma_18))));
+    assert!((140..150).contains(&converted), "{}", converted);
+}
+
+#[test]
+fn srgb_profile_is_left_alone() {
+    let srgb = [b"para\0\0\0\0\0\x03\0\0".as_slice(), &fixed(2.4), &fixed(1.0 / 1.055), &fixed(0.055 / 1.055), &fixed(1.0 / 12.92), &fixed(0.04045)].concat();
+    assert_eq!(level(jpeg(128, Some(&profile(&srgb)))), level(jpeg(128, None)));
+}
@@ -0,0 +1,33 @@
+use tim::image::{RenderCache, RenderOptions};
+use tim::Image;
+
+const SIXEL_MAGENTA: &str = "#185";
+
+fn sprite() -> Image {
+    let mut im = image::RgbaImage::from_pixel(2, 2, image::Rgba([10, 20, 30, 255]));
+    im.put_pixel(1, 1, image::Rgba([10, 20, 30, 0]));
+    im.put_pixel(0, 1, image::Rgba([10, 20, 30, 1]));
+    Image::new(image::DynamicImage::ImageRgba8(im)).unwrap()
+}
+
+#[test]
+fn transparent_pixels_can_be_shown_as_magenta() {
+    let im = sprite();
+
+    let plain = im.to_ansi_string_sized(Some(2), None, &RenderOptions::default());
+    assert!(!plain.contains("255;0;255"));
+
+    let magenta = im.to_ansi_string_sized(Some(2), None, &RenderOptions { magenta: true, ..Default::default() });
+    assert_eq!(magenta.matches("255;0;255").count(), 1);
+}
+
+#[test]
+fn sixel_shows_transparent_pixels_as_magenta() {
+    let sixel = |options: &RenderOptions| {
+        let mut out = Vec::new();
+        sprite().draw_sixel(&mut out, &mut RenderCache::default(), options, (2, 1), (0, 0), (1.0, 1.0)).unwrap();
+        String::from_utf8(out).unwrap().matches(SIXEL_MAGENTA).count()
+    };
+    assert_eq!(sixel(&RenderOptions::default()), 1);
+    assert!(sixel(&RenderOptions { magenta: true, ..Default::default() }) > 1);
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use std::io::Cursor;
+
+use tim::image::{Background, LoadOptions, Raw, RawFormat, RenderCache, RenderOptions};
+use tim::Image;
+
+const PNG_BASE64: &str = "iVBORw0KGgo";
+
+#[test]
+fn kitty_draw_is_centered_in_the_given_grid() {
+    let mut out = Vec::new();
+    common::image(common::solid(4, 4, [200, 100, 50])).draw_kitty(&mut out, &mut RenderCache::default(), &RenderOptions::default(), (10, 5), (0, 0), (1.0, 1.0)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("\x1b[2;4H"), "{:?}", out);
+    assert!(out.contains("c=4,r=2"), "{:?}", out);
+}
+
+fn iterm2_payload(im: &Image, options: &RenderOptions) -> String {
+    let mut out = Vec::new();
+    im.draw_iterm2(&mut out, &mut RenderCache::default(), options, (10, 5), (0, 0), (1.0, 1.0)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    out.split_once("preserveAspectRatio=0:").unwrap().1.trim_end_matches('\x07').to_string()
+}
+
+fn png_bytes() -> Vec<u8> {
+    let mut data = Vec::new();
+    image::RgbaImage::from_fn(4, 4, |x, y| image::Rgba([(x * 60) as u8, (y * 60) as u8, 9, if x == y { 0 } else { 255 }])).write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png).unwrap();
+    data
+}
+
+#[test]
+fn iterm2_resends_the_original_png() {
+    let data = png_bytes();
+    let im = Image::load(Cursor::new(data.clone()), &LoadOptions::default()).unwrap();
+    assert_eq!(iterm2_payload(&im, &RenderOptions::default()).len(), data.len().div_ceil(3) * 4);
+}
+
+#[test]
+fn iterm2_encodes_raw_pixels_as_png() {
+    let raw = Raw { width: 2, height: 2, format: RawFormat::Rgb8 };
+    let im = Image::load(Cursor::new(vec![255; 12]), &LoadOptions { raw: Some(raw), ..Default::default() }).unwrap();
+    assert!(iterm2_payload(&im, &RenderOptions::default()).starts_with(PNG_BASE64));
+}
+
+#[test]
+fn iterm2_reencodes_when_magenta_or_a_background_is_set() {
+    let data = png_bytes();
+    let im = Image::load(Cursor::new(data.clone()), &LoadOptions::default()).unwrap();
+    let original = iterm2_payload(&im, &RenderOptions::default());
+    assert_ne!(iterm2_payload(&im, &RenderOptions { magenta: true, ..Default::default() }), original);
+    assert_ne!(iterm2_payload(&im, &RenderOptions { background: Background::Checkerboard, ..Default::default() }), original);
+}
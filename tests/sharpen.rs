@@ -0,0 +1,48 @@
+use tim::image::{RenderCache, RenderOptions};
+use tim::Image;
+
+fn columns(values: &[u8]) -> Image {
+    let im = image::GrayImage::from_fn(values.len() as u32, 2, |x, _| image::Luma([values[x as usize]]));
+    Image::new(image::DynamicImage::ImageLuma8(im)).unwrap()
+}
+
+#[test]
+fn sharpen_leaves_flat_areas_alone() {
+    let im = columns(&[100; 4]);
+    let sharpened = RenderOptions { sharpen: Some(1.0), ..Default::default() };
+    assert_eq!(im.to_ansi_string_sized(Some(4), None, &sharpened), im.to_ansi_string_sized(Some(4), None, &RenderOptions::default()));
+}
+
+#[test]
+fn sharpen_boosts_edges() {
+    let im = columns(&[100, 100, 150, 150]);
+    let plain = im.to_ansi_string_sized(Some(4), None, &RenderOptions::default());
+    assert!(!plain.contains("83;83;83"));
+
+    let sharpened = im.to_ansi_string_sized(Some(4), None, &RenderOptions { sharpen: Some(1.0), ..Default::default() });
+    assert!(sharpened.contains("83;83;83"));
+    assert!(sharpened.contains("167;167;167"));
+
+    let clamped = im.to_ansi_string_sized(Some(4), None, &RenderOptions { sharpen: Some(100.0), ..Default::default() });
+    assert!(clamped.contains("0;0;0"));
+    assert!(clamped.contains("255;255;255"));
+}
+
+type Draw = fn(&Image, &mut Vec<u8>, &RenderOptions) -> anyhow::Result<()>;
+
+#[test]
+fn sharpen_applies_to_graphics_protocols() {
+    let im = columns(&[60, 60, 190, 190]);
+    let sharpened = RenderOptions { sharpen: Some(1.0), ..Default::default() };
+    let draws: [Draw; 3] = [
+        |im, out, options| im.draw_kitty(out, &mut RenderCache::default(), options, (4, 1), (0, 0), (1.0, 1.0)),
+        |im, out, options| im.draw_sixel(out, &mut RenderCache::default(), options, (4, 1), (0, 0), (1.0, 1.0)),
+        |im, out, options| im.draw_iterm2(out, &mut RenderCache::default(), options, (4, 1), (0, 0), (1.0, 1.0)),
+    ];
+    for draw in draws {
+        let (mut plain, mut sharp) = (Vec::new(), Vec::new());
+        draw(&im, &mut plain, &RenderOptions::default()).unwrap();
+        draw(&im, &mut sharp, &sharpened).unwrap();
+        assert_ne!(plain, sharp);
+    }
+}
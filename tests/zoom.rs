@@ -0,0 +1,38 @@
+mod common;
+
+use common::{gradient, image};
+use tim::image::RenderOptions;
+
+#[test]
+fn uniform_zoom_scales_both_axes() {
+    let im = image(gradient(20, 10));
+    assert_eq!(im.size((1.0, 1.0)), (20, 10));
+    assert_eq!(im.size((0.5, 0.5)), (10, 5));
+    assert_eq!(im.size((2.0, 2.0)), (40, 20));
+    assert_eq!(im.view_pos((4, 6), (2.0, 2.0)), (8, 12));
+    assert_eq!(im.center((0.5, 0.5), 20, 10), (5, 2));
+}
+
+#[test]
+fn uniform_zoom_samples_the_same_pixels() {
+    let im = image(gradient(8, 6));
+    for y in 0..12 {
+        for x in 0..16 {
+            assert_eq!(im.pixel((x, y), (2.0, 2.0)), im.pixel((x / 2, y / 2), (1.0, 1.0)));
+        }
+    }
+}
+
+#[test]
+fn uniform_zoom_renders_like_fit() {
+    let im = image(gradient(20, 10));
+    let options = RenderOptions::default();
+    assert_eq!(im.to_ansi_string_at(10, &options, (0, 0), (0.5, 0.5)), im.to_ansi_string_sized(Some(10), None, &options));
+}
+
+#[test]
+fn axes_zoom_independently() {
+    let im = image(gradient(20, 10));
+    assert_eq!(im.size((2.0, 0.5)), (40, 5));
+    assert_eq!(im.pixel((3, 4), (2.0, 0.5)), im.pixel((1, 8), (1.0, 1.0)));
+}